@@ -0,0 +1,74 @@
+//! Compares `compress`, which copies the encoder's output into a `Vec`, with
+//! `compress_buf`, which hands back the encoder's own buffer.
+//!
+//! Run with `cargo bench --bench copy`. For each input size, prints the time
+//! per call of both functions and how much of `compress`'s time the copy
+//! accounts for, then the smallest size from which the copy consistently
+//! costs more than `THRESHOLD` percent.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use sega_cmp::{compress, compress_buf, Size};
+
+/// The share of `compress`'s time, in percent, at which the copy is considered to matter.
+const THRESHOLD: f64 = 5.0;
+
+/// Tile-like data: short runs broken up by literals, which compresses to
+/// roughly half its size.
+fn input(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| if i % 16 < 8 { (i / 64) as u8 } else { i as u8 })
+        .collect()
+}
+
+/// Returns the mean time per call of `f`, running it for at least half a second.
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let mut iterations = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {
+        f();
+        iterations += 1;
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    let mut results = Vec::new();
+    println!(
+        "{:>10} {:>14} {:>14} {:>8}",
+        "size", "compress", "compress_buf", "copy %"
+    );
+    for shift in (10..=26).step_by(2) {
+        let data = input(1 << shift);
+        let copying = time(|| {
+            black_box(compress(black_box(&data), Size::Word).unwrap());
+        });
+        let zero_copy = time(|| {
+            black_box(compress_buf(black_box(&data), Size::Word).unwrap());
+        });
+        let share =
+            100.0 * (copying.as_secs_f64() - zero_copy.as_secs_f64()) / copying.as_secs_f64();
+        println!(
+            "{:>10} {:>14?} {:>14?} {:>7.1}%",
+            data.len(),
+            copying,
+            zero_copy,
+            share
+        );
+        results.push((data.len(), share));
+    }
+
+    // Timings are noisy, so only count the copy as mattering from the point
+    // at which it matters for every larger size too
+    let crossover = results
+        .iter()
+        .rev()
+        .take_while(|&&(_, share)| share > THRESHOLD)
+        .last()
+        .map(|&(len, _)| len);
+    match crossover {
+        Some(len) => println!("The copy exceeds {}% from {} bytes", THRESHOLD, len),
+        None => println!("The copy never exceeds {}%", THRESHOLD),
+    }
+}
@@ -1,9 +1,62 @@
-// Builds the C component of the crate
+// Builds the C component of the crate, or links against a system copy of it
+// when the `system-libcmpr` feature is enabled
 
 extern crate cc;
 
+use std::env;
+use std::process::{self, Command, Stdio};
+
+/// Explains that no C compiler could be found and stops the build, rather than
+/// leaving `cc` to fail with an error which doesn't say what's missing.
+fn missing_compiler(reason: &str) -> ! {
+    println!("cargo:warning=No usable C compiler was found to build the bundled CMP encoder");
+    eprintln!(
+        "error: sega_cmp couldn't find a usable C compiler ({}).\n\
+         Install one (or set `CC` to point at one), or enable the `pure-rust` \
+         feature to use the crate's Rust port of the encoder instead; its output \
+         is identical.",
+        reason
+    );
+    process::exit(1);
+}
+
 fn main() {
-    cc::Build::new()
-        .file("compress_rtns.c")
-        .compile("compress_rtns");
+    // The Rust port of the encoder is used instead, so there's nothing to build or link
+    if env::var_os("CARGO_FEATURE_PURE_RUST").is_some() {
+        return;
+    }
+
+    if env::var_os("CARGO_FEATURE_SYSTEM_LIBCMPR").is_some() {
+        println!("cargo:rerun-if-env-changed=CMPR_LIB_DIR");
+        if let Some(dir) = env::var_os("CMPR_LIB_DIR") {
+            println!("cargo:rustc-link-search=native={}", dir.to_string_lossy());
+        }
+        println!("cargo:rustc-link-lib=dylib=cmpr");
+        return;
+    }
+
+    let mut build = cc::Build::new();
+    build.file("compress_rtns.c");
+    // Logs what each encoder routine is doing to stderr, and builds it
+    // unoptimized with debug info for stepping through in a debugger
+    if env::var_os("CARGO_FEATURE_C_DEBUG").is_some() {
+        build.define("CMP_DEBUG", None).debug(true).opt_level(0);
+    }
+
+    // `cc` picks a compiler name without checking it exists, so make sure it
+    // can at least be started before trying to build with it
+    let compiler = match build.try_get_compiler() {
+        Ok(compiler) => compiler,
+        Err(e) => missing_compiler(&e.to_string()),
+    };
+    let started = Command::new(compiler.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if let Err(e) = started {
+        missing_compiler(&format!("`{}`: {}", compiler.path().display(), e));
+    }
+
+    build.compile("compress_rtns");
 }
@@ -153,6 +153,7 @@ int cmpr_8bit(char* pData, int numBytes, char** outData, int* cmprSizeBytes){
 	unitSizeBytes = 1;
 	runtarget = 2;
 	*cmprSizeBytes = 0;
+	CMP_DEBUG_LOG("cmpr_8bit: compressing %d units\n", numBytes);
 
 	/* Allocate Memory for compressed data stream */
 	/* Assume the compressed data will not exceed the original size  */
@@ -205,6 +206,7 @@ int cmpr_8bit(char* pData, int numBytes, char** outData, int* cmprSizeBytes){
                 *cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_8bit: expansion with %d units left, %d bytes written\n", numBytes, *cmprSizeBytes);
 					return -1;
 				}
 				memcpy(pCmrData,&unmatchedCount,unitSizeBytes);           pCmrData++;
@@ -216,6 +218,7 @@ int cmpr_8bit(char* pData, int numBytes, char** outData, int* cmprSizeBytes){
 			*cmprSizeBytes += (unitSizeBytes*2);
 			if(*cmprSizeBytes > maxCmprSizeBytes){
 				printf("Error in compression, expansion occurred.\n");
+				CMP_DEBUG_LOG("cmpr_8bit: expansion with %d units left, %d bytes written\n", numBytes, *cmprSizeBytes);
 				return -1;
 			}
 			memcpy(pCmrData,&runLength,unitSizeBytes);   pCmrData++;
@@ -239,6 +242,7 @@ int cmpr_8bit(char* pData, int numBytes, char** outData, int* cmprSizeBytes){
 				*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_8bit: expansion with %d units left, %d bytes written\n", numBytes, *cmprSizeBytes);
 					return -1;
 				}
 	       	    memcpy(pCmrData,&unmatchedCount,unitSizeBytes);           pCmrData++;
@@ -260,12 +264,14 @@ int cmpr_8bit(char* pData, int numBytes, char** outData, int* cmprSizeBytes){
 		*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 		if(*cmprSizeBytes > maxCmprSizeBytes){
 			printf("Error in compression, expansion occurred.\n");
+			CMP_DEBUG_LOG("cmpr_8bit: expansion with %d units left, %d bytes written\n", numBytes, *cmprSizeBytes);
 			return -1;
 		}
 		memcpy(pCmrData,&unmatchedCount,unitSizeBytes);           pCmrData++;
 		memcpy(pCmrData,startLoc,u_unmatchedCount*unitSizeBytes); pCmrData+=u_unmatchedCount;
 	}
 
+	CMP_DEBUG_LOG("cmpr_8bit: compressed to %d bytes\n", *cmprSizeBytes);
 	return 0;
 }
 
@@ -293,6 +299,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 	unitSizeBytes = 2;
 	runtarget = 2;
 	*cmprSizeBytes = 0;
+	CMP_DEBUG_LOG("cmpr_16bit: compressing %d units\n", numShorts);
 
 	/* Allocate Memory for compressed data stream */
 	/* Assume the compressed data will not exceed the original size  */
@@ -346,6 +353,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 				*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_16bit: expansion with %d units left, %d bytes written\n", numShorts, *cmprSizeBytes);
 					return -1;
 				}
 				swap16(&unmatchedCount);
@@ -358,6 +366,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 			*cmprSizeBytes += (unitSizeBytes*2);
 			if(*cmprSizeBytes > maxCmprSizeBytes){
 				printf("Error in compression, expansion occurred.\n");
+				CMP_DEBUG_LOG("cmpr_16bit: expansion with %d units left, %d bytes written\n", numShorts, *cmprSizeBytes);
 				return -1;
 			}
 
@@ -382,6 +391,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 				*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_16bit: expansion with %d units left, %d bytes written\n", numShorts, *cmprSizeBytes);
 					return -1;
 				}
 		        swap16(&unmatchedCount);
@@ -404,6 +414,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 		*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 		if(*cmprSizeBytes > maxCmprSizeBytes){
 			printf("Error in compression, expansion occurred.\n");
+			CMP_DEBUG_LOG("cmpr_16bit: expansion with %d units left, %d bytes written\n", numShorts, *cmprSizeBytes);
 			return -1;
 		}
 		swap16(&unmatchedCount);
@@ -411,6 +422,7 @@ int cmpr_16bit(short* pData, int numShorts, short** outData, int* cmprSizeBytes)
 		memcpy(pCmrData,startLoc,u_unmatchedCount*unitSizeBytes); pCmrData+=u_unmatchedCount;
 	}
 
+	CMP_DEBUG_LOG("cmpr_16bit: compressed to %d bytes\n", *cmprSizeBytes);
 	return 0;
 }
 
@@ -438,6 +450,7 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 	unitSizeBytes = 4;
 	runtarget = 2;
 	*cmprSizeBytes = 0;
+	CMP_DEBUG_LOG("cmpr_32bit: compressing %d units\n", numLongs);
 
 	/* Allocate Memory for compressed data stream */
 	/* Assume the compressed data will not exceed the original size  */
@@ -491,6 +504,7 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 				*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_32bit: expansion with %d units left, %d bytes written\n", numLongs, *cmprSizeBytes);
 					return -1;
 				}
 
@@ -504,6 +518,7 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 			*cmprSizeBytes += (unitSizeBytes*2);
 			if(*cmprSizeBytes > maxCmprSizeBytes){
 				printf("Error in compression, expansion occurred.\n");
+				CMP_DEBUG_LOG("cmpr_32bit: expansion with %d units left, %d bytes written\n", numLongs, *cmprSizeBytes);
 				return -1;
 			}
 
@@ -529,6 +544,7 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 				*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 				if(*cmprSizeBytes > maxCmprSizeBytes){
 					printf("Error in compression, expansion occurred.\n");
+					CMP_DEBUG_LOG("cmpr_32bit: expansion with %d units left, %d bytes written\n", numLongs, *cmprSizeBytes);
 					return -1;
 				}
 
@@ -552,6 +568,7 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 		*cmprSizeBytes += u_unmatchedCount*unitSizeBytes + unitSizeBytes;
 		if(*cmprSizeBytes > maxCmprSizeBytes){
 			printf("Error in compression, expansion occurred.\n");
+			CMP_DEBUG_LOG("cmpr_32bit: expansion with %d units left, %d bytes written\n", numLongs, *cmprSizeBytes);
 			return -1;
 		}
 		swap32(&unmatchedCount);
@@ -559,5 +576,6 @@ int cmpr_32bit(int* pData, int numLongs, int** outData, int* cmprSizeBytes){
 		memcpy(pCmrData,startLoc,u_unmatchedCount*unitSizeBytes); pCmrData+=u_unmatchedCount;
 	}
 
+	CMP_DEBUG_LOG("cmpr_32bit: compressed to %d bytes\n", *cmprSizeBytes);
 	return 0;
 }
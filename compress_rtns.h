@@ -25,6 +25,13 @@
 #define MIN_S_SHORT		-32768
 #define MIN_S_LONG		(-2147483647 - 1)
 
+//Diagnostics on stderr, enabled by defining CMP_DEBUG
+#ifdef CMP_DEBUG
+#define CMP_DEBUG_LOG(...) fprintf(stderr, __VA_ARGS__)
+#else
+#define CMP_DEBUG_LOG(...)
+#endif
+
 //Fctn Prototypes
 int cmp_compress(char* inputFname, unsigned int fileOffset, 
 				 int dataSizeBytes, int cmprType, 
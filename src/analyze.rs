@@ -0,0 +1,506 @@
+//! Analysis of uncompressed data, independent of how it ends up being encoded.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::Write;
+
+use crate::encode::{max_literal, max_run, CountingSink, RunEncoder, Sink};
+use crate::{check_alignment, check_input, compress_detailed, CompressionError, Size};
+
+/// A stretch of input in which the same unit repeats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Run {
+    /// The offset of the first unit of the run, in bytes.
+    pub start: usize,
+    /// The length of the repeated unit, in bytes.
+    pub unit_len: usize,
+    /// The number of times the unit repeats; always at least 2.
+    pub count: usize,
+}
+
+impl Run {
+    /// The number of bytes the run covers.
+    pub fn byte_len(&self) -> usize {
+        self.unit_len * self.count
+    }
+}
+
+/// Returns every run of two or more identical units of `size` in `data`, in order.
+///
+/// Each run is as long as possible, so runs never touch one another. Unlike
+/// the commands the encoder produces, runs aren't split at the longest length
+/// a single command can hold, and short runs the encoder might fold into a
+/// literal are still reported. Any trailing bytes which don't make up a whole
+/// unit are ignored.
+pub fn find_runs(data: &[u8], size: Size) -> Vec<Run> {
+    let unit_len = size.unit_len();
+    let mut runs = Vec::new();
+    let mut units = data.chunks_exact(unit_len).enumerate().peekable();
+    while let Some((index, unit)) = units.next() {
+        let mut count = 1;
+        while units.next_if(|&(_, next)| next == unit).is_some() {
+            count += 1;
+        }
+        if count >= 2 {
+            runs.push(Run {
+                start: index * unit_len,
+                unit_len,
+                count,
+            });
+        }
+    }
+    runs
+}
+
+/// Writes every run `find_runs` finds in `data` to `w` as CSV, for studying an
+/// asset's structure in a spreadsheet.
+///
+/// The first line is the header `offset,run_length,value`. Each following
+/// line holds one run: its offset in bytes, its length in units of `size`,
+/// and the repeated unit in hexadecimal, as it appears in `data` - such as
+/// `0x0041` for a word. `data` must be an even multiple of `size`; errors
+/// from `w` are returned as `CompressionError::Io`.
+pub fn export_run_stats<W: Write>(
+    data: &[u8],
+    size: Size,
+    w: &mut W,
+) -> Result<(), CompressionError> {
+    check_alignment(data.len(), size)?;
+    writeln!(w, "offset,run_length,value")?;
+    for run in find_runs(data, size) {
+        write!(w, "{},{},0x", run.start, run.count)?;
+        for byte in &data[run.start..run.start + run.unit_len] {
+            write!(w, "{:02X}", byte)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// The outcome of compressing data in one `Size`, as returned by `compress_all_modes`.
+#[derive(Debug)]
+pub struct ModeResult {
+    /// The size the data was compressed in.
+    pub size: Size,
+    /// The compressed payload, or why the data couldn't be compressed in this size.
+    pub compressed: Result<Vec<u8>, CompressionError>,
+}
+
+impl ModeResult {
+    /// The length of the compressed payload, or `None` if compression failed.
+    pub fn compressed_len(&self) -> Option<usize> {
+        self.compressed
+            .as_ref()
+            .ok()
+            .map(|compressed| compressed.len())
+    }
+}
+
+/// One mode's encoder in `encode_all_modes`, along with where its current run
+/// began.
+struct ModeScan<O: Sink> {
+    encoder: Option<RunEncoder<O>>,
+    run_start: usize,
+    /// How many runs of a single unit came before the current run and haven't
+    /// been passed to the encoder yet; these are always part of a literal, so
+    /// they're handed over together
+    singles: usize,
+}
+
+impl<O: Sink> ModeScan<O> {
+    /// Notes whether `unit` equals the one before it, ending the current run
+    /// if it doesn't.
+    #[inline(always)]
+    fn step(&mut self, data: &[u8], unit: usize, same: bool) {
+        if same {
+            return;
+        }
+        let len = unit - self.run_start;
+        if len == 1 {
+            self.singles += 1;
+        } else {
+            self.push(data, len);
+        }
+        self.run_start = unit;
+    }
+
+    /// Passes the pending single units, then the run of `len` units at
+    /// `run_start`, to the encoder.
+    fn push(&mut self, data: &[u8], len: usize) {
+        if let Some(ref mut encoder) = self.encoder {
+            encoder.push_literal(data, self.singles);
+            encoder.push_run(data, self.run_start, len);
+            // There's no point carrying on once the mode is bound to fail
+            if encoder.expanded() {
+                self.encoder = None;
+            }
+        }
+        self.singles = 0;
+    }
+
+    fn finish(mut self, data: &[u8], size: Size) -> Result<O, CompressionError> {
+        let units = data.len() / size.unit_len();
+        if units > self.run_start {
+            self.push(data, units - self.run_start);
+        }
+        match self.encoder {
+            Some(encoder) => encoder.finish(data),
+            // Either `data` doesn't fit the mode, or it was abandoned early
+            None => check_alignment(data.len(), size).and(Err(CompressionError::EncoderFailed)),
+        }
+    }
+}
+
+/// Splits `data` into maximal runs of identical units in every `Size` at once,
+/// passing each mode's runs to its encoder, and returns each encoder's result.
+///
+/// This is the analysis `compress_all_modes` and `exact_sizes` share. `data`
+/// is read a longword at a time, and each longword is compared with the one
+/// before it as a whole, as two words and as four bytes, so a single pass finds
+/// where the runs in every mode begin and end. A mode which `data`'s length
+/// isn't a multiple of is given no encoder, and its result is a `Misaligned`
+/// error.
+fn encode_all_modes<O: Sink, F: Fn() -> O>(
+    data: &[u8],
+    sink: F,
+) -> [Result<O, CompressionError>; 3] {
+    let scan = |size: Size| ModeScan {
+        encoder: check_alignment(data.len(), size)
+            .ok()
+            .map(|_| RunEncoder::new(size, data.len(), sink())),
+        // The first run begins at the first unit
+        run_start: 0,
+        singles: 0,
+    };
+    let (mut byte, mut word, mut longword) =
+        (scan(Size::Byte), scan(Size::Word), scan(Size::Longword));
+
+    let mut previous = 0;
+    for (k, chunk) in data.chunks_exact(4).enumerate() {
+        let current = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        // Each byte of this is zero where a byte equals the one before it
+        let bytes = current ^ (current >> 8 | previous << 24);
+        if k > 0 {
+            // Inside a run of a single byte, nothing changes in any mode
+            if bytes == 0 && current == previous {
+                continue;
+            }
+            longword.step(data, k, current == previous);
+            word.step(data, 2 * k, current >> 16 == previous & 0xFFFF);
+            byte.step(data, 4 * k, bytes >> 24 == 0);
+        }
+        word.step(data, 2 * k + 1, current & 0xFFFF == current >> 16);
+        byte.step(data, 4 * k + 1, bytes & 0x00FF_0000 == 0);
+        byte.step(data, 4 * k + 2, bytes & 0x0000_FF00 == 0);
+        byte.step(data, 4 * k + 3, bytes & 0x0000_00FF == 0);
+        previous = current;
+    }
+    // Whatever's left over is too short for a longword
+    let tail = data.len() / 4 * 4;
+    for i in tail.max(1)..data.len() {
+        byte.step(data, i, data[i] == data[i - 1]);
+        if i % 2 == 1 && i >= 3 {
+            word.step(data, i / 2, data[i - 1..=i] == data[i - 3..i - 1]);
+        }
+    }
+
+    [
+        byte.finish(data, Size::Byte),
+        word.finish(data, Size::Word),
+        longword.finish(data, Size::Longword),
+    ]
+}
+
+/// Compresses `data` in byte, word and longword mode, returning the result
+/// for each in that order.
+///
+/// Each payload is identical to calling `compress` in that mode, and a mode
+/// `data`'s length isn't a multiple of holds a `Misaligned` error. Rather than
+/// compressing `data` three times, this scans it once, finding the runs in all
+/// three modes together and encoding each mode's output from them as it goes,
+/// with the same command choices as the stock encoder. Stretches where a
+/// single byte repeats are skipped a longword at a time in every mode at once,
+/// so on data with long runs this is around three times as fast as three calls
+/// to `compress`; on data which is mostly literals, where each mode still has
+/// a command to choose for nearly every unit, it's about as fast. A mode whose
+/// output grows larger than the input is abandoned as soon as that happens,
+/// as `compress` would fail.
+///
+/// Returns `CompressionError::InputTooLarge` if `data` is too large to
+/// compress in any mode.
+pub fn compress_all_modes(data: &[u8]) -> Result<[ModeResult; 3], CompressionError> {
+    check_input(data, Size::Byte)?;
+    let [byte, word, longword] = encode_all_modes(data, Vec::new);
+    Ok([
+        ModeResult {
+            size: Size::Byte,
+            compressed: byte,
+        },
+        ModeResult {
+            size: Size::Word,
+            compressed: word,
+        },
+        ModeResult {
+            size: Size::Longword,
+            compressed: longword,
+        },
+    ])
+}
+
+/// Compresses `data` in every `Size` its length is a multiple of, and returns
+/// the one giving the smallest output.
+///
+/// The sizes are compressed as with `compress_all_modes`. Ties go to the
+/// narrower size. Returns `CompressionError::EncoderFailed` if `data`
+/// couldn't be compressed in any size.
+pub fn best_size(data: &[u8]) -> Result<Size, CompressionError> {
+    let mut best: Option<(Size, usize)> = None;
+    for mode in compress_all_modes(data)? {
+        match mode.compressed {
+            Ok(compressed) => {
+                if best.is_none_or(|(_, len)| compressed.len() < len) {
+                    best = Some((mode.size, compressed.len()));
+                }
+            }
+            Err(CompressionError::EncoderFailed) | Err(CompressionError::Misaligned { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    best.map(|(size, _)| size)
+        .ok_or(CompressionError::EncoderFailed)
+}
+
+/// How data compressed in one `Size`, as reported by `compare_modes`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ModeStats {
+    /// The size the data was compressed in.
+    pub size: Size,
+    /// The length of the compressed payload, in bytes, not including a header.
+    pub compressed_len: usize,
+    /// The number of commands in the compressed payload.
+    pub commands: usize,
+}
+
+/// A side-by-side comparison of compressing the same data in two sizes, as
+/// returned by `compare_modes`.
+///
+/// Its `Display` implementation prints a one-line summary of the comparison.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ModeComparison {
+    /// How the data compressed in the first size.
+    pub a: ModeStats,
+    /// How the data compressed in the second size.
+    pub b: ModeStats,
+    /// The size with the shorter payload, or `None` if they're the same length.
+    pub winner: Option<Size>,
+    /// How many bytes shorter the winner's payload is.
+    pub delta: usize,
+}
+
+impl fmt::Display for ModeComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for mode in &[self.a, self.b] {
+            write!(
+                f,
+                "{:?}: {} bytes in {} commands; ",
+                mode.size, mode.compressed_len, mode.commands
+            )?;
+        }
+        match self.winner {
+            Some(size) => write!(f, "{:?} is {} bytes smaller", size, self.delta),
+            None => write!(f, "both are the same length"),
+        }
+    }
+}
+
+/// Compresses `data` in both `a` and `b`, as with `compress_detailed`, and
+/// compares the results.
+///
+/// This is a diagnostic for understanding why one size suits some data better
+/// than another: fewer, longer commands in a wider size can outweigh each
+/// command's count being wider too. Returns an error if `data` can't be
+/// compressed in either size.
+pub fn compare_modes(data: &[u8], a: Size, b: Size) -> Result<ModeComparison, CompressionError> {
+    let stats = |size| {
+        compress_detailed(data, size).map(|(compressed, commands)| ModeStats {
+            size,
+            compressed_len: compressed.len(),
+            commands,
+        })
+    };
+    let (a, b) = (stats(a)?, stats(b)?);
+    let winner = match a.compressed_len.cmp(&b.compressed_len) {
+        Ordering::Less => Some(a.size),
+        Ordering::Greater => Some(b.size),
+        Ordering::Equal => None,
+    };
+    Ok(ModeComparison {
+        a,
+        b,
+        winner,
+        delta: a.compressed_len.abs_diff(b.compressed_len),
+    })
+}
+
+/// Returns exactly how long `compress`'s output would be for `data` in each of
+/// `Size::Byte`, `Size::Word` and `Size::Longword`, in that order, without
+/// building any of the output.
+///
+/// All three lengths come from a single pass over `data`, the same one
+/// `compress_all_modes` makes, which finds the runs in every mode together and
+/// counts the bytes each mode's commands would take instead of writing them.
+/// Its command choices are identical to the C encoder's. A length is `None` if
+/// `data`'s length isn't a multiple of that size, or if `compress` would fail
+/// because the output would be larger than the input. Unlike `suggest_size`,
+/// this isn't an estimate; it's cheaper than `compress_all_modes` when only
+/// the lengths are needed.
+pub fn exact_sizes(data: &[u8]) -> Result<[Option<usize>; 3], CompressionError> {
+    check_input(data, Size::Byte)?;
+    Ok(encode_all_modes(data, CountingSink::default)
+        .map(|sink| sink.ok().map(|sink| sink.written())))
+}
+
+/// Estimates the compressed length of a stream of units of one width, as
+/// they're fed to it one byte at a time.
+struct Estimate {
+    size: Size,
+    width: usize,
+    unit: [u8; 4],
+    filled: usize,
+    previous: Option<[u8; 4]>,
+    run: usize,
+    literal: usize,
+    cost: usize,
+}
+
+impl Estimate {
+    fn new(size: Size) -> Estimate {
+        Estimate {
+            size,
+            width: size.unit_len(),
+            unit: [0; 4],
+            filled: 0,
+            previous: None,
+            run: 0,
+            literal: 0,
+            cost: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.unit[self.filled] = byte;
+        self.filled += 1;
+        if self.filled < self.width {
+            return;
+        }
+        self.filled = 0;
+        if self.previous == Some(self.unit) {
+            self.run += 1;
+        } else {
+            self.end_run();
+            self.previous = Some(self.unit);
+            self.run = 1;
+        }
+    }
+
+    /// Like the encoder, a run of two only gets its own command if it doesn't
+    /// interrupt a literal.
+    fn end_run(&mut self) {
+        if self.run >= 3 || (self.run == 2 && self.literal == 0) {
+            self.end_literal();
+            self.cost += self.run.div_ceil(max_run(self.size)) * 2 * self.width;
+        } else {
+            self.literal += self.run;
+        }
+        self.run = 0;
+    }
+
+    fn end_literal(&mut self) {
+        let commands = self.literal.div_ceil(max_literal(self.size));
+        self.cost += (commands + self.literal) * self.width;
+        self.literal = 0;
+    }
+
+    /// Returns the estimated compressed length, or `None` if the input wasn't
+    /// a whole number of units.
+    fn finish(mut self) -> Option<usize> {
+        if self.filled != 0 {
+            return None;
+        }
+        self.end_run();
+        self.end_literal();
+        Some(self.cost)
+    }
+}
+
+/// The longest run `run_savings_table` reports on, in units: enough to show
+/// what happens past the end of the longest byte-mode run.
+const SAVINGS_TABLE_LEN: usize = 2 * 129 + 3;
+
+/// Returns how many bytes a run of each length from 1 to 261 units saves by
+/// being encoded as runs, rather than as part of the literal around it.
+///
+/// This is derived from the command format, not from compressing anything.
+/// Encoding a run in the middle of a literal costs two units for the run
+/// command, plus one for the count of the literal which resumes after it, so
+/// a run of 3 units only breaks even, and each unit beyond that saves one
+/// more unit. Runs longer than a command can hold (129 units in byte mode)
+/// take another command, and 1 or 2 units left over are left in the literal,
+/// so savings stay flat for two units past each multiple of that length.
+/// Runs at the very start of the data, or next to another run, don't need a
+/// literal to resume and save one unit more. Each entry is `(run length in
+/// units, bytes saved)`; lengths which save nothing have 0.
+pub fn run_savings_table(size: Size) -> Vec<(usize, usize)> {
+    let width = size.unit_len();
+    let max_run = max_run(size);
+    (1..=SAVINGS_TABLE_LEN)
+        .map(|len| {
+            let (full, rest) = (len / max_run, len % max_run);
+            let (commands, leftover) = if rest >= 3 {
+                (full + 1, 0)
+            } else {
+                (full, rest)
+            };
+            // Each run command is a count and a unit, plus one count to resume the literal
+            let encoded = (2 * commands + leftover + 1) * width;
+            (len, (len * width).saturating_sub(encoded))
+        })
+        .collect()
+}
+
+/// Predicts which `Size` will compress `data` best, without compressing it.
+///
+/// `data` is scanned once, measuring its runs at every unit width and
+/// estimating how large each mode's output would be. This is a heuristic
+/// intended as a fast pre-filter for very large inputs: the estimate ignores
+/// some of the encoder's finer decisions, so it may occasionally differ from
+/// `best_size`, which compresses the data in every mode to find out. Only
+/// sizes `data`'s length is a multiple of are suggested, and ties go to the
+/// narrower size.
+pub fn suggest_size(data: &[u8]) -> Size {
+    let sizes = [Size::Byte, Size::Word, Size::Longword];
+    let mut estimates = [
+        Estimate::new(Size::Byte),
+        Estimate::new(Size::Word),
+        Estimate::new(Size::Longword),
+    ];
+    for &byte in data {
+        for estimate in estimates.iter_mut() {
+            estimate.push(byte);
+        }
+    }
+
+    let mut best = (Size::Byte, usize::MAX);
+    for (size, estimate) in sizes.iter().zip(estimates) {
+        if let Some(cost) = estimate.finish() {
+            if cost < best.1 {
+                best = (*size, cost);
+            }
+        }
+    }
+    best.0
+}
@@ -0,0 +1,177 @@
+//! A minimal container for storing several named CMP blocks in one file.
+//!
+//! An archive is laid out as follows, with all integers big-endian:
+//!
+//! * the magic bytes `CMPA`
+//! * the number of entries, as a 32-bit integer
+//! * a directory with one record per entry: the length of its name as a 16-bit
+//!   integer, the UTF-8 name itself, and the offset of its block from the start
+//!   of the archive as a 32-bit integer
+//! * each entry's block, a CMP header followed by its compressed payload
+//!
+//! Blocks are ordinary header-prefixed CMP streams, so a loader which has
+//! read the directory can hand a block straight to a stock decoder.
+
+use crate::{compress_with_header, decompress_with_header, CompressionError, Size};
+
+const MAGIC: &[u8; 4] = b"CMPA";
+
+/// A collection of named CMP blocks, which can be written out as a single file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Archive {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Archive {
+    /// Creates an empty archive.
+    pub fn new() -> Archive {
+        Archive {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Compresses `data` in increments of `size` and stores it under `name`,
+    /// replacing any existing entry with the same name.
+    ///
+    /// Names longer than 65535 bytes can't be stored in the directory, and
+    /// are rejected with `CompressionError::InvalidArchive`. So is an entry
+    /// which would push any block's offset past the 32 bits the directory
+    /// holds it in; the archive is left as it was.
+    pub fn add(&mut self, name: &str, data: &[u8], size: Size) -> Result<(), CompressionError> {
+        if name.len() > u16::MAX as usize {
+            return Err(CompressionError::InvalidArchive);
+        }
+        let block = compress_with_header(data, size)?;
+        let replaced = self.entries.iter().position(|(n, _)| n == name);
+        let layout = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, (n, b))| {
+                let len = if Some(i) == replaced {
+                    block.len()
+                } else {
+                    b.len()
+                };
+                (n.len(), len)
+            })
+            .chain(replaced.map_or(Some((name.len(), block.len())), |_| None));
+        if last_offset(layout) > u32::MAX as usize {
+            return Err(CompressionError::InvalidArchive);
+        }
+        match replaced {
+            Some(i) => self.entries[i].1 = block,
+            None => self.entries.push((name.to_string(), block)),
+        }
+        Ok(())
+    }
+
+    /// Decompresses the entry stored under `name`, or returns `None` if there isn't one.
+    pub fn get(&self, name: &str) -> Option<Result<Vec<u8>, CompressionError>> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, block)| decompress_with_header(block))
+    }
+
+    /// The names of the archive's entries, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Writes the archive out in the format described in the module documentation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let directory_len: usize = self
+            .entries
+            .iter()
+            .map(|(name, _)| 2 + name.len() + 4)
+            .sum();
+        let mut offset = MAGIC.len() + 4 + directory_len;
+
+        // `add` keeps every offset within 32 bits, and with it the entry count
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for (name, block) in &self.entries {
+            out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&(offset as u32).to_be_bytes());
+            offset += block.len();
+        }
+        for (_, block) in &self.entries {
+            out.extend_from_slice(block);
+        }
+        out
+    }
+
+    /// Reads an archive written by `to_bytes`.
+    ///
+    /// Each entry is taken to run from its offset to the next entry's offset,
+    /// or to the end of `data` for the last entry. Blocks aren't decompressed
+    /// until they're requested with `get`.
+    pub fn from_bytes(data: &[u8]) -> Result<Archive, CompressionError> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(CompressionError::InvalidArchive);
+        }
+        let count = reader.read_u32()? as usize;
+
+        let mut directory = Vec::new();
+        for _ in 0..count {
+            let name_len = reader.read_u16()? as usize;
+            let name = std::str::from_utf8(reader.take(name_len)?)
+                .map_err(|_| CompressionError::InvalidArchive)?;
+            let offset = reader.read_u32()? as usize;
+            directory.push((name.to_string(), offset));
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for (i, (name, start)) in directory.iter().enumerate() {
+            let end = directory.get(i + 1).map_or(data.len(), |next| next.1);
+            if *start < reader.pos || *start > end || end > data.len() {
+                return Err(CompressionError::InvalidArchive);
+            }
+            entries.push((name.clone(), data[*start..end].to_vec()));
+        }
+        Ok(Archive { entries })
+    }
+}
+
+/// Returns the offset `to_bytes` would give the last of a list of entries, each
+/// given as the lengths of its name and block.
+fn last_offset<I: Iterator<Item = (usize, usize)>>(entries: I) -> usize {
+    let mut offset = MAGIC.len() + 4;
+    let mut last_block = 0;
+    for (name_len, block_len) in entries {
+        offset = offset.saturating_add(2 + name_len + 4 + last_block);
+        last_block = block_len;
+    }
+    offset
+}
+
+/// Reads the directory at the start of an archive.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompressionError> {
+        if len > self.data.len() - self.pos {
+            return Err(CompressionError::InvalidArchive);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, CompressionError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CompressionError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
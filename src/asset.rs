@@ -0,0 +1,80 @@
+//! A small fixed framing around a CMP block, for homebrew loaders which want
+//! to identify and size an asset before decompressing it.
+//!
+//! **This is this crate's own format, not part of CMP**; stock Saturn games
+//! don't know about it. An asset is laid out as follows, with all integers
+//! big-endian:
+//!
+//! * the magic bytes `CMPS`
+//! * the width the data was compressed in, as a 16-bit integer: 0 for bytes,
+//!   1 for words or 2 for longwords, matching `compress_rtns.h`
+//! * two bytes of padding, which are always zero
+//! * the decompressed size in bytes, as a 32-bit integer
+//! * a CMP header followed by the compressed payload, as written by
+//!   `compress_with_header`
+//!
+//! The framing is 12 bytes long, so a block loaded at a longword-aligned
+//! address stays aligned for any width, and can be handed to a stock decoder
+//! once the framing is skipped.
+
+use std::convert::TryFrom;
+
+use crate::header::parse_header;
+use crate::{compress_with_header, decompress_with_header, CompressionError, Size};
+
+const MAGIC: &[u8; 4] = b"CMPS";
+
+/// The length of the framing before the CMP block.
+const FRAMING_LEN: usize = 12;
+
+fn size_code(size: Size) -> u16 {
+    match size {
+        Size::Byte => 0,
+        Size::Word => 1,
+        Size::Longword => 2,
+    }
+}
+
+/// Compresses `data` in increments of `size`, and wraps the header-prefixed
+/// result in the framing described in the module documentation.
+///
+/// Data larger than 4 GiB can't have its size recorded, and is rejected with
+/// `CompressionError::InputTooLarge`.
+pub fn to_saturn_asset(data: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
+    let len = u32::try_from(data.len()).map_err(|_| CompressionError::InputTooLarge)?;
+    let block = compress_with_header(data, size)?;
+
+    let mut asset = Vec::with_capacity(FRAMING_LEN + block.len());
+    asset.extend_from_slice(MAGIC);
+    asset.extend_from_slice(&size_code(size).to_be_bytes());
+    asset.extend_from_slice(&[0, 0]);
+    asset.extend_from_slice(&len.to_be_bytes());
+    asset.extend_from_slice(&block);
+    Ok(asset)
+}
+
+/// Decompresses an asset written by `to_saturn_asset`, returning the data
+/// along with the width it was compressed in.
+///
+/// Returns `CompressionError::InvalidAsset` if the framing is missing or
+/// malformed, or disagrees with the CMP header which follows it; the block
+/// itself is then checked as with `decompress_with_header`.
+pub fn from_saturn_asset(asset: &[u8]) -> Result<(Vec<u8>, Size), CompressionError> {
+    if asset.len() < FRAMING_LEN || &asset[..4] != MAGIC || asset[6..8] != [0, 0] {
+        return Err(CompressionError::InvalidAsset);
+    }
+    let size = match u16::from_be_bytes([asset[4], asset[5]]) {
+        0 => Size::Byte,
+        1 => Size::Word,
+        2 => Size::Longword,
+        _ => return Err(CompressionError::InvalidAsset),
+    };
+    let len = u32::from_be_bytes([asset[8], asset[9], asset[10], asset[11]]);
+
+    let block = &asset[FRAMING_LEN..];
+    let header = parse_header(block)?;
+    if header.size != size || header.decompressed_size != len {
+        return Err(CompressionError::InvalidAsset);
+    }
+    Ok((decompress_with_header(block)?, size))
+}
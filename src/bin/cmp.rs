@@ -0,0 +1,69 @@
+//! Commandline tool for working with CMP files.
+//!
+//! Usage:
+//!
+//! ```text
+//! cmp info [--json] <file>
+//! ```
+
+use std::env;
+use std::fs;
+use std::process;
+
+use sega_cmp::{parse_header, HeaderWidth};
+
+const USAGE: &str = "Usage: cmp info [--json] <file>";
+
+fn info(args: &[String]) -> Result<(), String> {
+    let mut json = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or_else(|| USAGE.to_string())?;
+
+    let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let header = parse_header(&data).map_err(|e| format!("{}: {}", path, e))?;
+
+    let width = match header.width {
+        HeaderWidth::Bits16 => 16,
+        HeaderWidth::Bits32 => 32,
+    };
+    let payload_len = data.len() - header.encoded_len();
+    let ratio = if header.decompressed_size == 0 {
+        1.0
+    } else {
+        payload_len as f64 / header.decompressed_size as f64
+    };
+
+    if json {
+        println!(
+            "{{\"decompressed_size\":{},\"size\":\"{:?}\",\"header_width\":{},\"payload_len\":{},\"ratio\":{:.4}}}",
+            header.decompressed_size, header.size, width, payload_len, ratio
+        );
+    } else {
+        println!("Decompressed size: {} bytes", header.decompressed_size);
+        println!("Size:              {:?}", header.size);
+        println!("Header width:      {}-bit", width);
+        println!("Payload length:    {} bytes", payload_len);
+        println!("Ratio:             {:.1}%", ratio * 100.0);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("info") => info(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,55 @@
+//! Owning the C encoder's output buffer directly.
+
+use std::fmt;
+use std::ops::Deref;
+use std::slice;
+
+use libc::c_void;
+
+/// Compressed data left in the buffer the C encoder allocated for it.
+///
+/// `compress` copies the encoder's output into a `Vec` and frees the
+/// original; a `CompressedBuf` skips that copy, and frees the buffer when
+/// it's dropped instead. It derefs to `[u8]`.
+pub struct CompressedBuf {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The buffer is uniquely owned, and only ever read through shared references
+unsafe impl Send for CompressedBuf {}
+unsafe impl Sync for CompressedBuf {}
+
+impl CompressedBuf {
+    /// Takes ownership of `len` bytes at `ptr`, which must have been allocated
+    /// with `malloc` and not be null.
+    pub(crate) unsafe fn from_raw(ptr: *mut u8, len: usize) -> CompressedBuf {
+        CompressedBuf { ptr, len }
+    }
+}
+
+impl Deref for CompressedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for CompressedBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for CompressedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CompressedBuf").field(&&**self).finish()
+    }
+}
+
+impl Drop for CompressedBuf {
+    fn drop(&mut self) {
+        unsafe { libc::free(self.ptr as *mut c_void) };
+    }
+}
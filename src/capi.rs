@@ -0,0 +1,179 @@
+//! A C API for embedding the crate in emulators and tools written in other languages.
+//!
+//! Enabled by the `capi` feature. To build a shared library, run
+//! `cargo rustc --lib --release --features capi --crate-type cdylib`. The functions
+//! are declared in C as:
+//!
+//! ```c
+//! uint32_t cmp_abi_version(void);
+//! int32_t cmp_decompress(const uint8_t* input, size_t input_len,
+//!                        uint8_t* out, size_t out_cap, size_t* out_len);
+//! int32_t cmp_compress_buffer(const uint8_t* input, size_t input_len, int32_t size,
+//!                             uint8_t* out, size_t out_cap, size_t* out_len);
+//! ```
+//!
+//! The compress entry point isn't named `cmp_compress` because the bundled
+//! C encoder already defines a function with that name.
+//!
+//! Both functions return one of the `CMP_*` status codes. The signatures and
+//! status codes will only change along with `CMP_ABI_VERSION`.
+
+use std::slice;
+
+use crate::decode::block_len;
+use crate::{
+    compress_with_header, decompress_with_options, parse_header, CompressOptions, CompressionError,
+    Size,
+};
+
+/// The version of this API, as returned by `cmp_abi_version`.
+pub const CMP_ABI_VERSION: u32 = 1;
+
+/// The call succeeded.
+pub const CMP_OK: i32 = 0;
+/// A required pointer was null, or `size` wasn't 0, 1 or 2.
+pub const CMP_INVALID_ARGUMENT: i32 = -1;
+/// The output buffer is too small; `out_len` holds the size it needs to be.
+pub const CMP_BUFFER_TOO_SMALL: i32 = -2;
+/// The input's length isn't a multiple of the requested size.
+pub const CMP_MISALIGNED: i32 = -3;
+/// The encoder couldn't compress the input without expanding it.
+pub const CMP_ENCODER_FAILED: i32 = -4;
+/// The compressed input is malformed.
+pub const CMP_CORRUPT_INPUT: i32 = -5;
+/// Any other failure, such as input too large to compress.
+pub const CMP_OTHER_ERROR: i32 = -6;
+
+fn status(error: &CompressionError) -> i32 {
+    match *error {
+        CompressionError::Misaligned { .. } => CMP_MISALIGNED,
+        CompressionError::EncoderFailed => CMP_ENCODER_FAILED,
+        CompressionError::Truncated
+        | CompressionError::InvalidHeader
+        | CompressionError::UnknownSizeByte(_)
+        | CompressionError::SizeMismatch { .. } => CMP_CORRUPT_INPUT,
+        _ => CMP_OTHER_ERROR,
+    }
+}
+
+/// Copies `data` to `out`, or reports how large `out` needs to be.
+unsafe fn write_output(data: &[u8], out: *mut u8, out_cap: usize, out_len: *mut usize) -> i32 {
+    *out_len = data.len();
+    if data.len() > out_cap {
+        return CMP_BUFFER_TOO_SMALL;
+    }
+    if !data.is_empty() {
+        if out.is_null() {
+            return CMP_INVALID_ARGUMENT;
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), out, data.len());
+    }
+    CMP_OK
+}
+
+/// Views `input_len` bytes at `input` as a slice, allowing null for an empty input.
+unsafe fn input_slice<'a>(input: *const u8, input_len: usize) -> Option<&'a [u8]> {
+    if input_len == 0 {
+        Some(&[])
+    } else if input.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(input, input_len))
+    }
+}
+
+/// Returns `CMP_ABI_VERSION`, so callers can check they were built against
+/// a compatible version of this API.
+#[no_mangle]
+pub extern "C" fn cmp_abi_version() -> u32 {
+    CMP_ABI_VERSION
+}
+
+/// Decompresses the header-prefixed CMP stream in `input` into `out`.
+///
+/// On success, or with `CMP_BUFFER_TOO_SMALL`, `*out_len` is set to the
+/// decompressed size. The size is read from the header and compared with
+/// `out_cap` before anything is decoded, so a buffer which is too small never
+/// costs a decode.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes, `out` to `out_cap`
+/// writable bytes, and `out_len` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn cmp_decompress(
+    input: *const u8,
+    input_len: usize,
+    out: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let data = match input_slice(input, input_len) {
+        Some(data) if !out_len.is_null() => data,
+        _ => return CMP_INVALID_ARGUMENT,
+    };
+    let header = match parse_header(data) {
+        Ok(header) => header,
+        Err(e) => return status(&e),
+    };
+    // Checked before decoding, so a header declaring gigabytes can't make a
+    // caller with a small buffer wait for them to be allocated
+    let declared = header.decompressed_size as usize;
+    if declared > out_cap {
+        *out_len = declared;
+        return CMP_BUFFER_TOO_SMALL;
+    }
+    let end = match block_len(data) {
+        Ok(end) => end,
+        Err(e) => return status(&e),
+    };
+    // The last command may run on past the declared size into padding
+    let width = header.size.unit_len();
+    let options = CompressOptions {
+        max_output: Some(out_cap.max(declared.div_ceil(width) * width)),
+        ..CompressOptions::default()
+    };
+    match decompress_with_options(&data[header.encoded_len()..end], header.size, options) {
+        Ok(mut decompressed) => {
+            decompressed.truncate(declared);
+            write_output(&decompressed, out, out_cap, out_len)
+        }
+        Err(e) => status(&e),
+    }
+}
+
+/// Compresses `input` in increments of `size` - 0 for bytes, 1 for words, or 2
+/// for longwords, matching `compress_rtns.h` - writing a header followed by the
+/// compressed payload to `out`.
+///
+/// On success, or with `CMP_BUFFER_TOO_SMALL`, `*out_len` is set to the
+/// length of the header and payload.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes, `out` to `out_cap`
+/// writable bytes, and `out_len` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn cmp_compress_buffer(
+    input: *const u8,
+    input_len: usize,
+    size: i32,
+    out: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let data = match input_slice(input, input_len) {
+        Some(data) if !out_len.is_null() => data,
+        _ => return CMP_INVALID_ARGUMENT,
+    };
+    let size = match size {
+        0 => Size::Byte,
+        1 => Size::Word,
+        2 => Size::Longword,
+        _ => return CMP_INVALID_ARGUMENT,
+    };
+    match compress_with_header(data, size) {
+        Ok(compressed) => write_output(&compressed, out, out_cap, out_len),
+        Err(e) => status(&e),
+    }
+}
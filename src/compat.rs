@@ -0,0 +1,60 @@
+//! Reports which formats and encoder this build of the crate supports.
+
+use libc::c_int;
+
+use crate::{max_size_for_width, HeaderWidth, Size};
+
+/// The encoder a build of the crate compiles in, chosen by its features.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// The C encoder in `compress_rtns.c`, built and statically linked.
+    /// This is the default.
+    BundledC,
+    /// A shared `libcmpr`, linked by the `system-libcmpr` feature.
+    SystemLibcmpr,
+    /// The Rust port of the C encoder, selected by the `pure-rust` feature.
+    PureRust,
+}
+
+/// What this build of the crate can compress, as returned by `compatibility_info`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CompatibilityInfo {
+    /// The encoder `compress` and everything built on it uses.
+    pub backend: Backend,
+    /// Whether the bundled C encoder was built with the `c-debug` feature's
+    /// logging. Always false for the other backends.
+    pub c_debug: bool,
+    /// The widths data can be compressed in.
+    pub sizes: &'static [Size],
+    /// The header widths `create_header` and `parse_header` understand.
+    pub header_widths: &'static [HeaderWidth],
+    /// The longest input, in bytes, the encoder accepts; longer input is
+    /// rejected with `InputTooLarge`.
+    pub max_input_len: usize,
+    /// The largest decompressed size, in bytes, a header can declare.
+    pub max_decompressed_size: u32,
+}
+
+/// Describes the formats this build of the crate supports, and which encoder
+/// it was built with.
+///
+/// Whichever backend is in use, the output is identical; this is for checking
+/// that a build is configured as expected, such as asserting in CI that the C
+/// encoder is the one being tested.
+pub fn compatibility_info() -> CompatibilityInfo {
+    let backend = if cfg!(feature = "pure-rust") {
+        Backend::PureRust
+    } else if cfg!(feature = "system-libcmpr") {
+        Backend::SystemLibcmpr
+    } else {
+        Backend::BundledC
+    };
+    CompatibilityInfo {
+        backend,
+        c_debug: backend == Backend::BundledC && cfg!(feature = "c-debug"),
+        sizes: &[Size::Byte, Size::Word, Size::Longword],
+        header_widths: &[HeaderWidth::Bits16, HeaderWidth::Bits32],
+        max_input_len: c_int::MAX as usize,
+        max_decompressed_size: max_size_for_width(HeaderWidth::Bits32),
+    }
+}
@@ -0,0 +1,576 @@
+//! Decompression of CMP streams.
+//!
+//! A CMP stream is a sequence of commands, each beginning with a signed,
+//! big-endian count the same width as the data being decompressed.
+//! A count of zero or more is a run: the following unit is repeated
+//! `count + 2` times. A negative count is a literal: the following
+//! `-count` units are copied as-is.
+
+use std::io::Write;
+
+use crate::encode::{max_run, CompressOptions};
+use crate::header::{parse_header, Header, HeaderWidth};
+use crate::{CompressionError, Size};
+
+/// A single command read from a compressed stream.
+#[derive(Copy, Clone)]
+pub(crate) enum Command<'a> {
+    /// `count` copies of `unit`
+    Run { unit: &'a [u8], count: usize },
+    /// Units to be copied directly
+    Literal { units: &'a [u8] },
+}
+
+impl<'a> Command<'a> {
+    /// The number of bytes this command produces once decompressed.
+    pub(crate) fn decompressed_len(&self) -> usize {
+        match *self {
+            Command::Run { unit, count } => unit.len() * count,
+            Command::Literal { units } => units.len(),
+        }
+    }
+
+    fn write_to(&self, out: &mut Vec<u8>) {
+        match *self {
+            Command::Run { unit, count } => {
+                for _ in 0..count {
+                    out.extend_from_slice(unit);
+                }
+            }
+            Command::Literal { units } => out.extend_from_slice(units),
+        }
+    }
+
+    /// Writes only the first `len` bytes this command produces, which may end
+    /// partway through a unit.
+    fn write_prefix_to(&self, out: &mut Vec<u8>, len: usize) {
+        self.write_range_to(out, 0, len);
+    }
+
+    /// Writes `len` bytes of this command's output, beginning `start` bytes in;
+    /// either end may fall partway through a unit.
+    fn write_range_to(&self, out: &mut Vec<u8>, start: usize, len: usize) {
+        match *self {
+            Command::Run { unit, .. } => {
+                let mut offset = start % unit.len();
+                let mut remaining = len;
+                while remaining > 0 {
+                    let take = (unit.len() - offset).min(remaining);
+                    out.extend_from_slice(&unit[offset..offset + take]);
+                    remaining -= take;
+                    offset = 0;
+                }
+            }
+            Command::Literal { units } => out.extend_from_slice(&units[start..start + len]),
+        }
+    }
+}
+
+/// Iterates over the commands in a compressed payload.
+///
+/// After an error, the iterator yields nothing further.
+pub(crate) struct Commands<'a> {
+    data: &'a [u8],
+    pos: usize,
+    size: Size,
+    width: usize,
+    extended_runs: bool,
+    literal_marker: Option<u8>,
+}
+
+impl<'a> Commands<'a> {
+    pub(crate) fn new(data: &'a [u8], size: Size) -> Commands<'a> {
+        Commands::with_options(data, size, CompressOptions::default())
+    }
+
+    /// Iterates over a payload which may use the non-stock commands enabled by `options`.
+    pub(crate) fn with_options(
+        data: &'a [u8],
+        size: Size,
+        options: CompressOptions,
+    ) -> Commands<'a> {
+        Commands {
+            data,
+            pos: 0,
+            size,
+            width: size.unit_len(),
+            // `max_run` takes precedence, so no extended runs were written
+            extended_runs: options.extended_runs && options.max_run.is_none(),
+            literal_marker: options.literal_marker,
+        }
+    }
+
+    /// The number of payload bytes consumed so far.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Consumes the next `len` bytes of the payload, checking first that
+    /// that many bytes remain.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompressionError> {
+        if len > self.data.len() - self.pos {
+            return Err(CompressionError::Truncated);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_count(&mut self) -> Result<i64, CompressionError> {
+        let bytes = self.take(self.width)?;
+        Ok(match *bytes {
+            [a] => a as i8 as i64,
+            [a, b] => i16::from_be_bytes([a, b]) as i64,
+            [a, b, c, d] => i32::from_be_bytes([a, b, c, d]) as i64,
+            _ => unreachable!(),
+        })
+    }
+
+    fn read_command(&mut self) -> Result<Command<'a>, CompressionError> {
+        let count = self.read_count()?;
+        if count >= 0 {
+            let mut count = count as usize + 2;
+            // In an extended run, the largest count is followed by a 32-bit
+            // count of additional repeats
+            if self.extended_runs && count == max_run(self.size) {
+                let bytes = self.take(4)?;
+                count += u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+            }
+            let unit = self.take(self.width)?;
+            Ok(Command::Run { unit, count })
+        } else {
+            if let Some(marker) = self.literal_marker {
+                // The marker is padded with zeroes to a whole unit
+                let unit = self.take(self.width)?;
+                if unit[0] != marker {
+                    return Err(CompressionError::UnexpectedLiteralMarker(unit[0]));
+                }
+                if let Some(&found) = unit[1..].iter().find(|&&byte| byte != 0) {
+                    return Err(CompressionError::UnexpectedLiteralMarker(found));
+                }
+            }
+            let len = ((-count) as usize)
+                .checked_mul(self.width)
+                .ok_or(CompressionError::Truncated)?;
+            let units = self.take(len)?;
+            Ok(Command::Literal { units })
+        }
+    }
+}
+
+impl<'a> Iterator for Commands<'a> {
+    type Item = Result<Command<'a>, CompressionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let command = self.read_command();
+        if command.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(command)
+    }
+}
+
+/// Decompresses a headerless CMP payload which was compressed in increments of `size`.
+///
+/// The entire payload is decompressed; trailing data which isn't a complete
+/// command results in an error. Nothing in a bare payload records what width
+/// it was compressed with, and decoding it in the wrong one can succeed
+/// with garbage output, so use `decompress_with_header`, which reads the width
+/// from the header, whenever the header is available.
+pub fn decompress(data: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
+    decompress_with_options(data, size, CompressOptions::default())
+}
+
+/// Decompresses a headerless payload compressed by `compress_with_options`
+/// with the same `options`.
+///
+/// Options which only affect how hard the encoder works, such as `effort`,
+/// make no difference here; options which change the command format, such as
+/// `extended_runs` and `literal_marker`, must match the ones used to compress.
+/// `max_output` caps how much the payload may decompress to.
+pub fn decompress_with_options(
+    data: &[u8],
+    size: Size,
+    options: CompressOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut out = Vec::new();
+    for command in Commands::with_options(data, size, options) {
+        let command = command?;
+        if let Some(limit) = options.max_output {
+            if command.decompressed_len() > limit - out.len() {
+                return Err(CompressionError::OutputLimitExceeded { limit });
+            }
+        }
+        command.write_to(&mut out);
+    }
+    Ok(out)
+}
+
+/// How many bytes of a run `decompress_to` and `decompress_each` build up
+/// before passing them on.
+const RUN_BUF_LEN: usize = 4096;
+
+/// Decodes a headerless payload, passing its output to `f` a run or literal
+/// command at a time, and returns the number of bytes produced.
+fn for_each_chunk<F>(data: &[u8], size: Size, mut f: F) -> Result<usize, CompressionError>
+where
+    F: FnMut(&[u8]) -> Result<(), CompressionError>,
+{
+    let mut written = 0;
+    let mut run_buf = Vec::with_capacity(RUN_BUF_LEN);
+    for command in Commands::new(data, size) {
+        let command = command?;
+        match command {
+            Command::Run { unit, count } => {
+                let mut remaining = count;
+                while remaining > 0 {
+                    let units = remaining.min(RUN_BUF_LEN / unit.len());
+                    run_buf.clear();
+                    for _ in 0..units {
+                        run_buf.extend_from_slice(unit);
+                    }
+                    f(&run_buf)?;
+                    remaining -= units;
+                }
+            }
+            Command::Literal { units } => f(units)?,
+        }
+        written += command.decompressed_len();
+    }
+    Ok(written)
+}
+
+/// Decompresses a headerless CMP payload which was compressed in increments of
+/// `size`, writing the output to `w` as it's produced, and returns the number
+/// of bytes written.
+///
+/// Only one command's output - or, for long runs, a few KiB of it - is held in
+/// memory at a time. Errors from `w` are returned as `CompressionError::Io`.
+/// If the payload turns out to be malformed, everything before the bad command
+/// will already have been written.
+pub fn decompress_to<W: Write>(
+    data: &[u8],
+    size: Size,
+    w: &mut W,
+) -> Result<usize, CompressionError> {
+    for_each_chunk(data, size, |chunk| Ok(w.write_all(chunk)?))
+}
+
+/// Decompresses a headerless CMP payload which was compressed in increments of
+/// `size`, passing the output to `sink` as it's produced, and returns the
+/// number of bytes produced.
+///
+/// This suits loaders which feed each piece of output straight to hardware.
+/// `sink` is called once for each literal command, with its units borrowed
+/// straight from `data`, and once for each run command, or once for each
+/// 4 KiB piece of a longer run; every chunk is a whole number of units, and
+/// none is empty. Each command is checked to lie within `data` before any of
+/// its output is passed on, so if the payload turns out to be malformed,
+/// `sink` will already have seen everything before the bad command, and the
+/// error is returned.
+pub fn decompress_each<F: FnMut(&[u8])>(
+    data: &[u8],
+    size: Size,
+    mut sink: F,
+) -> Result<usize, CompressionError> {
+    for_each_chunk(data, size, |chunk| {
+        sink(chunk);
+        Ok(())
+    })
+}
+
+/// Returns the number of bytes a headerless CMP payload will decompress to,
+/// without decompressing it.
+///
+/// The payload is checked to be well-formed the same way `decompress` checks it,
+/// so this can be used to rebuild the header for a payload whose header has been lost.
+pub fn decompressed_len(data: &[u8], size: Size) -> Result<usize, CompressionError> {
+    let mut len = 0;
+    for command in Commands::new(data, size) {
+        len += command?.decompressed_len();
+    }
+    Ok(len)
+}
+
+/// Decompresses a CMP stream which begins with a header, as written by `create_header`.
+///
+/// The width of the data is read from the header, and decompression stops once
+/// the header's declared size has been produced; any data after that is ignored.
+/// If the last command produces more than the declared size - as when data was
+/// padded to a whole number of units before compressing, and the header records
+/// its unpadded size - the output is trimmed to exactly the declared size.
+/// Output shorter than the declared size is a `SizeMismatch` error.
+pub fn decompress_with_header(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    decompress_block(data).map(|(out, _)| out)
+}
+
+/// Decompresses the header and payload at the start of `data`, returning the
+/// decompressed data along with the number of bytes of `data` the block occupied.
+fn decompress_block(data: &[u8]) -> Result<(Vec<u8>, usize), CompressionError> {
+    decompress_block_as(data, parse_header(data)?)
+}
+
+/// Decompresses the block at the start of `data` as described by `header`,
+/// which has already been read from it.
+fn decompress_block_as(data: &[u8], header: Header) -> Result<(Vec<u8>, usize), CompressionError> {
+    // The header alone can declare up to 4 GiB, so the allocation is also
+    // bounded by what the payload could produce if it were nothing but the
+    // longest runs; any more than that would be a `SizeMismatch` anyway
+    let payload_len = data.len().saturating_sub(header.encoded_len());
+    let width = header.size.unit_len();
+    let bound =
+        (payload_len / (2 * width)).saturating_mul(max_run(header.size).saturating_mul(width));
+    let mut out = Vec::with_capacity((header.decompressed_size as usize).min(bound));
+    let len = walk_block(data, header, |command, len| {
+        command.write_prefix_to(&mut out, len)
+    })?;
+    Ok((out, len))
+}
+
+/// Returns the number of bytes the header and payload at the start of `data`
+/// occupy, checking that the payload decodes to the header's declared size.
+pub(crate) fn block_len(data: &[u8]) -> Result<usize, CompressionError> {
+    walk_block(data, parse_header(data)?, |_, _| {})
+}
+
+/// Reads the header of the block at the start of `data`, which may be followed
+/// by further blocks.
+///
+/// An empty block's 16-bit header followed by a Word or Longword block's
+/// header - `00 00 00 00 00 04 00 10`, say - also reads as a single 32-bit
+/// header. When the bytes after an empty 16-bit size field could begin another
+/// header and the block doesn't decode as a 32-bit one, it's read as an empty
+/// 16-bit block instead.
+fn sequence_header(data: &[u8]) -> Result<Header, CompressionError> {
+    let header = parse_header(data)?;
+    if header.width == HeaderWidth::Bits32
+        && parse_header(&data[4..]).is_ok()
+        && walk_block(data, header, |_, _| {}).is_err()
+    {
+        return Ok(Header {
+            decompressed_size: 0,
+            width: HeaderWidth::Bits16,
+            ..header
+        });
+    }
+    Ok(header)
+}
+
+/// Returns the number of bytes the block at the start of a sequence of
+/// back-to-back blocks occupies, reading its header as `BlockIter` does.
+pub(crate) fn sequence_block_len(data: &[u8]) -> Result<usize, CompressionError> {
+    walk_block(data, sequence_header(data)?, |_, _| {})
+}
+
+/// Passes each command of the block at the start of `data`, whose header has
+/// already been read into `header`, to `f`, stopping once the header's declared
+/// size has been produced, and returns the block's length.
+///
+/// `f` is also given the number of bytes of the command's output to keep, which
+/// is less than its full length only when it overshoots the declared size.
+fn walk_block<'a, F: FnMut(&Command<'a>, usize)>(
+    data: &'a [u8],
+    header: Header,
+    mut f: F,
+) -> Result<usize, CompressionError> {
+    let expected = header.decompressed_size as usize;
+
+    let mut produced = 0;
+    let mut commands = Commands::new(&data[header.encoded_len()..], header.size);
+    while produced < expected {
+        let command = match commands.next() {
+            Some(command) => command?,
+            None => break,
+        };
+        // Clamped before writing, so a corrupt run count can't allocate
+        // far more than the header asked for
+        let len = command.decompressed_len().min(expected - produced);
+        f(&command, len);
+        produced += len;
+    }
+
+    if produced != expected {
+        return Err(CompressionError::SizeMismatch {
+            expected,
+            actual: produced,
+        });
+    }
+    Ok(header.encoded_len() + commands.position())
+}
+
+/// A header-prefixed CMP stream whose header has been read, but whose payload
+/// is only decompressed on request.
+///
+/// Parsing a block only reads its header, so it's cheap enough to do for every
+/// entry in a large listing, leaving decompression until the data is needed.
+#[derive(Copy, Clone, Debug)]
+pub struct CmpBlock<'a> {
+    data: &'a [u8],
+    header: Header,
+}
+
+impl<'a> CmpBlock<'a> {
+    /// Reads the header at the start of `data`, without looking at the payload.
+    pub fn parse(data: &'a [u8]) -> Result<CmpBlock<'a>, CompressionError> {
+        let header = parse_header(data)?;
+        Ok(CmpBlock { data, header })
+    }
+
+    /// The block's header.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// The width of data the block was compressed with.
+    pub fn size(&self) -> Size {
+        self.header.size
+    }
+
+    /// The size the header declares the block decompresses to, in bytes.
+    ///
+    /// This is read from the header, and isn't checked against the payload
+    /// until the block is decompressed.
+    pub fn decompressed_len(&self) -> usize {
+        self.header.decompressed_size as usize
+    }
+
+    /// The compressed payload following the header, along with anything after it.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header.encoded_len()..]
+    }
+
+    /// Decompresses the block, as with `decompress_with_header`.
+    pub fn decompress(&self) -> Result<Vec<u8>, CompressionError> {
+        decompress_with_header(self.data)
+    }
+}
+
+/// Iterates over back-to-back header and payload blocks, yielding each block decompressed.
+///
+/// Each block's extent is found by decoding it until its header's declared size
+/// has been produced; the next block is expected to begin immediately afterwards.
+/// Iteration ends at the end of the data, or after the first malformed block.
+///
+/// An empty block followed by a Word or Longword block looks like a single
+/// block with a 32-bit header; it's only read as an empty block if it doesn't
+/// decode as a 32-bit one.
+pub struct BlockIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BlockIter<'a> {
+    /// Creates an iterator over the blocks in `data`.
+    pub fn new(data: &'a [u8]) -> BlockIter<'a> {
+        BlockIter { data }
+    }
+}
+
+impl<'a> Iterator for BlockIter<'a> {
+    type Item = Result<Vec<u8>, CompressionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let block =
+            sequence_header(self.data).and_then(|header| decompress_block_as(self.data, header));
+        match block {
+            Ok((block, len)) => {
+                self.data = &self.data[len..];
+                Some(Ok(block))
+            }
+            Err(e) => {
+                self.data = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Returns the total size of back-to-back header and payload blocks once
+/// decompressed, without decompressing any of them, as a check against a memory
+/// budget before extracting them with `BlockIter`.
+///
+/// Blocks aren't prefixed with their compressed length, so finding where one
+/// ends and the next begins still means walking its commands; they're checked
+/// the same way as in `BlockIter`, but nothing is written. Each block's header
+/// may be 16-bit or 32-bit independently of the others.
+pub fn total_decompressed_size(blocks: &[u8]) -> Result<usize, CompressionError> {
+    let mut data = blocks;
+    let mut total = 0;
+    while !data.is_empty() {
+        let header = sequence_header(data)?;
+        total += header.decompressed_size as usize;
+        data = &data[walk_block(data, header, |_, _| {})?..];
+    }
+    Ok(total)
+}
+
+/// Decompresses a headerless CMP payload a fixed number of bytes at a time,
+/// without holding the whole decompressed output in memory.
+///
+/// Chunk boundaries don't need to line up with commands or units: a long run
+/// may be spread across many chunks, and one chunk may hold the end of one
+/// command and the start of the next. Every chunk is exactly the requested
+/// length apart from the last, which may be shorter. Only one chunk is held in
+/// memory at a time, which is why this isn't an `Iterator`: each chunk borrows
+/// the decoder's buffer until the next call to `next_chunk`.
+pub struct ChunkedDecoder<'a> {
+    commands: Commands<'a>,
+    // The command being decoded, and how many of its bytes have been produced
+    current: Option<(Command<'a>, usize)>,
+    chunk_len: usize,
+    buf: Vec<u8>,
+}
+
+impl<'a> ChunkedDecoder<'a> {
+    /// Creates a decoder which will produce `chunk_len` bytes at a time from a
+    /// payload compressed in increments of `size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is 0.
+    pub fn new(data: &'a [u8], size: Size, chunk_len: usize) -> ChunkedDecoder<'a> {
+        assert!(chunk_len > 0, "chunk length must be non-zero");
+        ChunkedDecoder {
+            commands: Commands::new(data, size),
+            current: None,
+            chunk_len,
+            buf: Vec::with_capacity(chunk_len),
+        }
+    }
+
+    /// Decompresses the next chunk, returning `None` once the payload has
+    /// been fully decompressed.
+    ///
+    /// If the payload turns out to be malformed, the error is returned in
+    /// place of the chunk it occurred in, and no further chunks are produced.
+    pub fn next_chunk(&mut self) -> Option<Result<&[u8], CompressionError>> {
+        self.buf.clear();
+        while self.buf.len() < self.chunk_len {
+            let (command, done) = match self.current {
+                Some(current) => current,
+                None => match self.commands.next() {
+                    Some(Ok(command)) => (command, 0),
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => break,
+                },
+            };
+            let len = command.decompressed_len();
+            let take = (len - done).min(self.chunk_len - self.buf.len());
+            command.write_range_to(&mut self.buf, done, take);
+            self.current = if done + take < len {
+                Some((command, done + take))
+            } else {
+                None
+            };
+        }
+        if self.buf.is_empty() {
+            return None;
+        }
+        Some(Ok(&self.buf))
+    }
+}
@@ -0,0 +1,110 @@
+//! Compressing data as the difference from a reference buffer.
+//!
+//! **This isn't a stock CMP format.** A delta stream is an ordinary
+//! header-prefixed CMP stream, but what it decompresses to is `target` XORed
+//! with `base`; a loader has to XOR it with the same `base` to recover the
+//! original. When `target` is a small variation on `base`, such as one frame
+//! of an animation and the next, the XORed data is mostly zeros and compresses
+//! far better than `target` would alone.
+
+use crate::decode::block_len;
+use crate::{compress_with_header, decompress_with_header, CompressionError, Size};
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+/// Compresses `target` in increments of `size` as its difference from `base`,
+/// returning a header followed by the compressed payload.
+///
+/// `base` and `target` must be the same length; otherwise returns
+/// `CompressionError::LengthMismatch`.
+pub fn compress_delta(base: &[u8], target: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
+    if base.len() != target.len() {
+        return Err(CompressionError::LengthMismatch {
+            expected: base.len(),
+            actual: target.len(),
+        });
+    }
+    compress_with_header(&xor(base, target), size)
+}
+
+/// Reconstructs the data compressed by `compress_delta` against the same `base`.
+///
+/// Returns `CompressionError::LengthMismatch` if the stream doesn't decompress
+/// to the same length as `base`.
+pub fn decompress_delta(base: &[u8], compressed: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let delta = decompress_with_header(compressed)?;
+    if delta.len() != base.len() {
+        return Err(CompressionError::LengthMismatch {
+            expected: base.len(),
+            actual: delta.len(),
+        });
+    }
+    Ok(xor(base, &delta))
+}
+
+/// Marks a frame in a sequence which is compressed on its own.
+const KEY_FRAME: u32 = 0;
+/// Marks a frame in a sequence which is compressed as its difference from the previous frame.
+const DELTA_FRAME: u32 = 1;
+
+/// Compresses a sequence of frames, such as an animation, in increments of
+/// `size`, taking advantage of what each frame has in common with the one
+/// before it.
+///
+/// **This isn't a stock CMP format**, and has to be read with
+/// `decompress_sequence`. Each frame is written as a 32-bit big-endian tag
+/// followed by a header-prefixed CMP stream. A tag of 0 marks a key frame,
+/// whose stream decompresses to the frame itself; a tag of 1 marks a delta
+/// frame, whose stream is the frame XORed with the previous frame, as with
+/// `compress_delta`. The first frame is always a key frame; later frames are
+/// delta frames when they're the same length as the previous frame and the
+/// delta compresses smaller. The tags keep each stream aligned as well as the
+/// sequence itself is.
+pub fn compress_sequence(frames: &[&[u8]], size: Size) -> Result<Vec<u8>, CompressionError> {
+    let mut out = Vec::new();
+    let mut previous: Option<&[u8]> = None;
+    for &frame in frames {
+        let key = compress_with_header(frame, size);
+        let delta = match previous {
+            Some(base) if base.len() == frame.len() => compress_delta(base, frame, size).ok(),
+            _ => None,
+        };
+        let (tag, block) = match (key, delta) {
+            (Ok(key), Some(delta)) if delta.len() < key.len() => (DELTA_FRAME, delta),
+            (Ok(key), _) => (KEY_FRAME, key),
+            (Err(_), Some(delta)) => (DELTA_FRAME, delta),
+            (Err(e), None) => return Err(e),
+        };
+        out.extend_from_slice(&tag.to_be_bytes());
+        out.extend_from_slice(&block);
+        previous = Some(frame);
+    }
+    Ok(out)
+}
+
+/// Decompresses a sequence of frames written by `compress_sequence`.
+///
+/// Returns `CompressionError::InvalidSequence` if a frame's tag is unknown, or
+/// the first frame isn't a key frame.
+pub fn decompress_sequence(data: &[u8]) -> Result<Vec<Vec<u8>>, CompressionError> {
+    let mut frames: Vec<Vec<u8>> = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(CompressionError::Truncated);
+        }
+        let tag = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let block = &rest[4..];
+        let len = block_len(block)?;
+        let frame = match (tag, frames.last()) {
+            (KEY_FRAME, _) => decompress_with_header(&block[..len])?,
+            (DELTA_FRAME, Some(base)) => decompress_delta(base, &block[..len])?,
+            _ => return Err(CompressionError::InvalidSequence),
+        };
+        frames.push(frame);
+        rest = &block[len..];
+    }
+    Ok(frames)
+}
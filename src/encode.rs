@@ -0,0 +1,897 @@
+//! Compression implemented in Rust, for the cases the bundled C encoder doesn't cover.
+
+use std::collections::VecDeque;
+
+use crate::decode::{Command, Commands};
+use crate::{check_alignment, CompressionError, Size};
+
+/// How hard the encoder should work to shrink its output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Effort {
+    /// The single-pass encoder shared with Sega's original tool.
+    /// Its output is identical to Sega's encoder.
+    #[default]
+    Fast,
+    /// A slower encoder which chooses the combination of runs and literals
+    /// giving the smallest possible output. Where `Fast` commits to each run
+    /// as soon as it finds one, this weighs every run and literal length at
+    /// every position in a second, dynamic programming pass, so its output is
+    /// never larger than `Fast`'s. It can be read by any CMP decoder, but it
+    /// isn't byte-for-byte identical to Sega's encoder.
+    Best,
+}
+
+/// Options controlling how data is compressed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct CompressOptions {
+    /// How hard the encoder should work to shrink its output.
+    pub effort: Effort,
+    /// Encodes runs longer than a single stock command can hold as one
+    /// extended run command, rather than as several consecutive runs.
+    ///
+    /// An extended run is written as a run command with the largest count for
+    /// its width (`0x7F`, `0x7FFF` or `0x7FFFFFFF`), followed by a 32-bit
+    /// big-endian count of repeats beyond the longest stock run, followed by
+    /// the unit to repeat. A run of 129 + N bytes in byte mode is therefore
+    /// `7F`, `N` as four bytes, then the byte.
+    ///
+    /// **This doesn't produce a stock CMP stream** - the Saturn's decoder reads
+    /// the largest count as an ordinary run - so the result can only be read
+    /// by `decompress_with_options` with the same option set, or a custom
+    /// loader which understands the extension.
+    pub extended_runs: bool,
+    /// Follows the count of every literal command with this marker byte, for
+    /// game-specific decoders which expect one before the literal's units.
+    ///
+    /// With a marker of `0xA5`, a two-byte literal "BA" in byte mode is
+    /// written as `FE A5 42 41` rather than the stock `FE 42 41`. Run
+    /// commands are unaffected.
+    ///
+    /// In word and longword mode the marker takes up a whole unit - the marker
+    /// byte followed by zero bytes, so `A5 00` or `A5 00 00 00` - so that every
+    /// count and unit after it stays aligned to the unit width, as decoders
+    /// which read the payload a word or longword at a time require.
+    ///
+    /// **This doesn't produce a stock CMP stream** - a stock decoder would
+    /// read the marker as the first unit of the literal - so the result can
+    /// only be read by `decompress_with_options` with the same marker, or by
+    /// the game's own decoder. `None`, the default, writes stock literals.
+    pub literal_marker: Option<u8>,
+    /// Splits any run longer than this many units into several shorter runs,
+    /// for decoders in games which can't handle runs as long as the format
+    /// allows.
+    ///
+    /// Runs are split into pieces of at most `max_run` units; a single unit
+    /// left over is written as a one-unit literal. Values below 2 are treated
+    /// as 2, the shortest possible run, and values above the longest run a
+    /// stock command can hold have no effect. Every command the result
+    /// contains is a stock command, so it can be read by any CMP decoder, but
+    /// it's larger than the stock encoder's output whenever a run is split,
+    /// and may be larger than the input. When set, this takes precedence over
+    /// `extended_runs`. `None`, the default, leaves runs as long as possible.
+    pub max_run: Option<usize>,
+    /// The most bytes `decompress_with_options` will produce before giving up
+    /// with `CompressionError::OutputLimitExceeded`, for decoding untrusted
+    /// payloads: a single longword run command of 8 bytes can otherwise
+    /// expand to 8 GiB. Each command is checked before any of its output is
+    /// written. Compressing ignores this. `None`, the default, sets no limit.
+    pub max_output: Option<usize>,
+}
+
+/// The longest literal a single command can hold, in units.
+pub(crate) fn max_literal(size: Size) -> usize {
+    match size {
+        Size::Byte => 128,
+        Size::Word => 32768,
+        Size::Longword => 1 << 31,
+    }
+}
+
+/// Returns the most bytes compressing `input_len` bytes in increments of
+/// `size` can produce, for allocating an output buffer up front.
+///
+/// The worst case is input with no runs at all, which is stored as literals
+/// of the longest length a command allows (128, 32768 or 2^31 units), each
+/// costing one unit for its count:
+///
+/// ```text
+/// input_len + ceil(units / max_literal_units) * unit_len
+/// ```
+///
+/// where `units` is `input_len` divided by the unit length, rounded up. `compress`
+/// never exceeds `input_len` itself, since it fails rather than expanding, and
+/// `Effort::Best` never does worse than all literals, so this bounds both. It
+/// doesn't account for the extra bytes `extended_runs` or `literal_marker` add.
+pub fn max_compressed_len(input_len: usize, size: Size) -> usize {
+    let units = input_len.div_ceil(size.unit_len());
+    input_len + units.div_ceil(max_literal(size)) * size.unit_len()
+}
+
+/// The longest run a single command can hold, in units.
+pub(crate) fn max_run(size: Size) -> usize {
+    max_literal(size) + 1
+}
+
+/// Somewhere the greedy encoder can write its output.
+pub(crate) trait Sink {
+    /// The number of bytes written so far.
+    fn written(&self) -> usize;
+
+    fn push(&mut self, byte: u8);
+
+    fn extend_from_slice(&mut self, bytes: &[u8]);
+}
+
+impl Sink for Vec<u8> {
+    fn written(&self) -> usize {
+        self.len()
+    }
+
+    fn push(&mut self, byte: u8) {
+        Vec::push(self, byte)
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        Vec::extend_from_slice(self, bytes)
+    }
+}
+
+/// Counts the bytes written to it without keeping them, for measuring output.
+#[derive(Default)]
+pub(crate) struct CountingSink {
+    written: usize,
+}
+
+impl Sink for CountingSink {
+    fn written(&self) -> usize {
+        self.written
+    }
+
+    fn push(&mut self, _: u8) {
+        self.written += 1;
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.written += bytes.len();
+    }
+}
+
+/// A fixed-size buffer which counts, but drops, any bytes which don't fit,
+/// so that the space the whole output needs is known afterwards.
+struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    written: usize,
+}
+
+impl<'a> Sink for SliceSink<'a> {
+    fn written(&self) -> usize {
+        self.written
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.extend_from_slice(&[byte]);
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        let end = self.written + bytes.len();
+        if let Some(dest) = self.buf.get_mut(self.written..end) {
+            dest.copy_from_slice(bytes);
+        }
+        self.written = end;
+    }
+}
+
+/// Appends a command's count to `out` as a big-endian value `width` bytes wide.
+pub(crate) fn push_count<O: Sink>(out: &mut O, width: usize, count: i64) {
+    match width {
+        1 => out.push(count as i8 as u8),
+        2 => out.extend_from_slice(&(count as i16).to_be_bytes()),
+        _ => out.extend_from_slice(&(count as i32).to_be_bytes()),
+    }
+}
+
+/// Rewrites a stock payload so that consecutive runs of the same unit are
+/// merged into extended runs, as described on `CompressOptions::extended_runs`.
+pub(crate) fn extend_runs(payload: &[u8], size: Size) -> Vec<u8> {
+    let width = size.unit_len();
+    let longest_run = max_run(size);
+    let mut out = Vec::with_capacity(payload.len());
+
+    let push_run = |out: &mut Vec<u8>, unit: &[u8], mut count: usize| {
+        while count > 0 {
+            if count == 1 {
+                push_count(out, width, -1);
+            } else if count < longest_run {
+                push_count(out, width, count as i64 - 2);
+            } else {
+                let extra = (count - longest_run).min(u32::MAX as usize);
+                push_count(out, width, longest_run as i64 - 2);
+                out.extend_from_slice(&(extra as u32).to_be_bytes());
+                count -= extra;
+            }
+            out.extend_from_slice(unit);
+            count -= count.min(longest_run);
+        }
+    };
+
+    let mut pending: Option<(&[u8], usize)> = None;
+    // The payload came from our own encoder, so it's always well-formed
+    for command in Commands::new(payload, size).flatten() {
+        match command {
+            Command::Run { unit, count } => match pending {
+                Some((pending_unit, ref mut pending_count)) if pending_unit == unit => {
+                    *pending_count += count;
+                }
+                _ => {
+                    if let Some((pending_unit, pending_count)) = pending {
+                        push_run(&mut out, pending_unit, pending_count);
+                    }
+                    pending = Some((unit, count));
+                }
+            },
+            Command::Literal { units } => {
+                if let Some((pending_unit, pending_count)) = pending.take() {
+                    push_run(&mut out, pending_unit, pending_count);
+                }
+                push_count(&mut out, width, -((units.len() / width) as i64));
+                out.extend_from_slice(units);
+            }
+        }
+    }
+    if let Some((pending_unit, pending_count)) = pending {
+        push_run(&mut out, pending_unit, pending_count);
+    }
+    out
+}
+
+/// Rewrites a payload so that no run is longer than `limit` units, as
+/// described on `CompressOptions::max_run`.
+///
+/// `options` describes the format `payload` is already in, and mustn't have
+/// a literal marker set.
+pub(crate) fn limit_runs(
+    payload: &[u8],
+    size: Size,
+    options: CompressOptions,
+    limit: usize,
+) -> Vec<u8> {
+    let width = size.unit_len();
+    let limit = limit.clamp(2, max_run(size));
+    let mut out = Vec::with_capacity(payload.len());
+    // The payload came from our own encoder, so it's always well-formed
+    for command in Commands::with_options(payload, size, options).flatten() {
+        match command {
+            Command::Run { unit, mut count } => {
+                while count > 0 {
+                    if count == 1 {
+                        push_count(&mut out, width, -1);
+                        out.extend_from_slice(unit);
+                        break;
+                    }
+                    let mut len = count.min(limit);
+                    // Leave at least two units for the next run when possible
+                    if count - len == 1 && len > 2 {
+                        len -= 1;
+                    }
+                    push_count(&mut out, width, len as i64 - 2);
+                    out.extend_from_slice(unit);
+                    count -= len;
+                }
+            }
+            Command::Literal { units } => {
+                push_count(&mut out, width, -((units.len() / width) as i64));
+                out.extend_from_slice(units);
+            }
+        }
+    }
+    out
+}
+
+/// Rewrites a payload so that each literal command's count is followed by
+/// `marker`, padded with zeroes to a whole unit, as described on
+/// `CompressOptions::literal_marker`.
+///
+/// `options` describes the format `payload` is already in, and mustn't
+/// already have a literal marker set.
+pub(crate) fn mark_literals(
+    payload: &[u8],
+    size: Size,
+    options: CompressOptions,
+    marker: u8,
+) -> Vec<u8> {
+    let width = size.unit_len();
+    let mut out = Vec::with_capacity(payload.len());
+    let mut commands = Commands::with_options(payload, size, options);
+    let mut start = 0;
+    // The payload came from our own encoder, so it's always well-formed
+    while let Some(Ok(command)) = commands.next() {
+        let end = commands.position();
+        match command {
+            Command::Literal { .. } => {
+                out.extend_from_slice(&payload[start..start + width]);
+                out.push(marker);
+                out.resize(out.len() + width - 1, 0);
+                out.extend_from_slice(&payload[start + width..end]);
+            }
+            Command::Run { .. } => out.extend_from_slice(&payload[start..end]),
+        }
+        start = end;
+    }
+    out
+}
+
+/// Tracks the minimum value in a window of indices which only ever moves
+/// towards lower indices: new indices enter at the bottom of the window, and
+/// indices leave once they're above its top.
+struct WindowMin {
+    entries: VecDeque<(usize, u64)>,
+}
+
+impl WindowMin {
+    fn new() -> WindowMin {
+        WindowMin {
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn push(&mut self, index: usize, value: u64) {
+        while let Some(&(_, front)) = self.entries.front() {
+            if front < value {
+                break;
+            }
+            self.entries.pop_front();
+        }
+        self.entries.push_front((index, value));
+    }
+
+    fn expire_above(&mut self, top: usize) {
+        while let Some(&(index, _)) = self.entries.back() {
+            if index <= top {
+                break;
+            }
+            self.entries.pop_back();
+        }
+    }
+
+    fn min(&self) -> Option<(usize, u64)> {
+        self.entries.back().copied()
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Choice {
+    Literal(usize),
+    Run(usize),
+}
+
+/// Compresses `data` into the smallest possible stream of commands.
+///
+/// `data` must be a multiple of `size`'s unit length. The cost of encoding
+/// everything from each unit onwards is computed from the end of the data
+/// backwards, with every valid literal and run length considered at each
+/// position; sliding window minimums keep this linear in the length of the input.
+pub(crate) fn compress_optimal(data: &[u8], size: Size) -> Vec<u8> {
+    let width = size.unit_len();
+    let n = data.len() / width;
+    let unit = |i: usize| &data[i * width..(i + 1) * width];
+
+    let max_literal = max_literal(size);
+    let max_run = max_run(size);
+
+    // cost[i] is the smallest number of units needed to encode units i..n
+    let mut cost = vec![0u64; n + 1];
+    let mut choices = vec![Choice::Literal(1); n];
+
+    // Literal windows are keyed by `j + cost[j]`, run windows by `cost[j]`
+    let mut literals = WindowMin::new();
+    let mut runs = WindowMin::new();
+    let mut in_run = false;
+
+    for i in (0..n).rev() {
+        literals.push(i + 1, (i + 1) as u64 + cost[i + 1]);
+        literals.expire_above(i.saturating_add(max_literal));
+        let (end, best) = literals.min().unwrap();
+        cost[i] = best - i as u64 + 1;
+        choices[i] = Choice::Literal(end - i);
+
+        if i + 1 < n && unit(i) == unit(i + 1) {
+            if !in_run {
+                runs.clear();
+                in_run = true;
+            }
+            runs.push(i + 2, cost[i + 2]);
+            runs.expire_above(i.saturating_add(max_run));
+            let (end, best) = runs.min().unwrap();
+            if best + 2 < cost[i] {
+                cost[i] = best + 2;
+                choices[i] = Choice::Run(end - i);
+            }
+        } else {
+            in_run = false;
+        }
+    }
+
+    let mut out = Vec::with_capacity(cost[0] as usize * width);
+    let mut i = 0;
+    while i < n {
+        match choices[i] {
+            Choice::Literal(len) => {
+                push_count(&mut out, width, -(len as i64));
+                out.extend_from_slice(&data[i * width..(i + len) * width]);
+                i += len;
+            }
+            Choice::Run(len) => {
+                push_count(&mut out, width, len as i64 - 2);
+                out.extend_from_slice(unit(i));
+                i += len;
+            }
+        }
+    }
+    out
+}
+
+/// Input to the greedy encoder, addressed in units.
+pub(crate) trait Source {
+    /// The length of the input in bytes.
+    fn byte_len(&self) -> usize;
+
+    /// The unit at index `i`, zero-padded to four bytes.
+    fn unit(&self, i: usize) -> [u8; 4];
+
+    /// Whether the units at indices `a` and `b` are equal.
+    fn eq(&self, a: usize, b: usize) -> bool {
+        self.unit(a) == self.unit(b)
+    }
+
+    /// Counts how many consecutive units starting at index `from` are equal
+    /// to the unit at index `pattern`, stopping after `max`.
+    fn count_equal(&self, pattern: usize, from: usize, max: usize) -> usize {
+        (from..from + max)
+            .take_while(|&i| self.eq(pattern, i))
+            .count()
+    }
+
+    /// Appends `count` units, beginning with the unit at index `start`, to `out`.
+    fn extend<O: Sink>(&self, out: &mut O, start: usize, count: usize);
+}
+
+/// A single contiguous buffer.
+pub(crate) struct Contiguous<'a> {
+    data: &'a [u8],
+    width: usize,
+}
+
+impl<'a> Contiguous<'a> {
+    pub(crate) fn new(data: &'a [u8], size: Size) -> Contiguous<'a> {
+        Contiguous {
+            data,
+            width: size.unit_len(),
+        }
+    }
+}
+
+impl<'a> Source for Contiguous<'a> {
+    fn byte_len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    fn unit(&self, i: usize) -> [u8; 4] {
+        let mut unit = [0; 4];
+        unit[..self.width].copy_from_slice(&self.data[i * self.width..(i + 1) * self.width]);
+        unit
+    }
+
+    #[inline]
+    fn eq(&self, a: usize, b: usize) -> bool {
+        match self.width {
+            1 => self.data[a] == self.data[b],
+            width => self.data[a * width..(a + 1) * width] == self.data[b * width..(b + 1) * width],
+        }
+    }
+
+    fn count_equal(&self, pattern: usize, from: usize, max: usize) -> usize {
+        let width = self.width;
+        let units = &self.data[from * width..(from + max) * width];
+        if width == 1 {
+            let unit = self.data[pattern];
+            return units.iter().take_while(|&&other| other == unit).count();
+        }
+        let unit = &self.data[pattern * width..(pattern + 1) * width];
+        units
+            .chunks_exact(width)
+            .take_while(|&other| other == unit)
+            .count()
+    }
+
+    fn extend<O: Sink>(&self, out: &mut O, start: usize, count: usize) {
+        out.extend_from_slice(&self.data[start * self.width..(start + count) * self.width]);
+    }
+}
+
+/// Several buffers treated as if they were concatenated.
+/// A unit may straddle the boundary between two buffers.
+struct Segments<'a> {
+    segments: &'a [&'a [u8]],
+    // The byte offset each segment begins at
+    starts: Vec<usize>,
+    len: usize,
+    width: usize,
+}
+
+impl<'a> Segments<'a> {
+    fn new(segments: &'a [&'a [u8]], size: Size) -> Segments<'a> {
+        let mut starts = Vec::with_capacity(segments.len());
+        let mut len = 0;
+        for segment in segments {
+            starts.push(len);
+            len += segment.len();
+        }
+        Segments {
+            segments,
+            starts,
+            len,
+            width: size.unit_len(),
+        }
+    }
+
+    /// Calls `f` with each piece of the bytes in `start..end`, in order.
+    fn for_each_piece<F: FnMut(&[u8])>(&self, mut start: usize, end: usize, mut f: F) {
+        // The last segment beginning at or before `start`, which skips over empty segments
+        let mut index = self.starts.partition_point(|&s| s <= start) - 1;
+        while start < end {
+            let segment = self.segments[index];
+            let offset = start - self.starts[index];
+            let take = (segment.len() - offset).min(end - start);
+            f(&segment[offset..offset + take]);
+            start += take;
+            index += 1;
+        }
+    }
+}
+
+impl<'a> Source for Segments<'a> {
+    fn byte_len(&self) -> usize {
+        self.len
+    }
+
+    fn unit(&self, i: usize) -> [u8; 4] {
+        let mut unit = [0; 4];
+        let mut filled = 0;
+        self.for_each_piece(i * self.width, (i + 1) * self.width, |piece| {
+            unit[filled..filled + piece.len()].copy_from_slice(piece);
+            filled += piece.len();
+        });
+        unit
+    }
+
+    fn extend<O: Sink>(&self, out: &mut O, start: usize, count: usize) {
+        self.for_each_piece(start * self.width, (start + count) * self.width, |piece| {
+            out.extend_from_slice(piece)
+        });
+    }
+}
+
+/// Compresses `data` with the same single-pass algorithm as the bundled C
+/// encoder, appending the commands to `out`.
+///
+/// The output is byte-for-byte identical to the C encoder's, and like the C
+/// encoder this fails if the output would be larger than the input; on failure,
+/// `out` may contain part of the output. `data` must be a multiple of `size`'s
+/// unit length.
+pub(crate) fn compress_greedy<S: Source, O: Sink>(
+    data: &S,
+    size: Size,
+    out: &mut O,
+) -> Result<(), CompressionError> {
+    compress_greedy_with_progress(data, size, out, |_| {})
+}
+
+/// How many bytes of input `compress_greedy_with_progress` consumes between
+/// progress reports.
+const PROGRESS_INTERVAL: usize = 64 * 1024;
+
+/// `compress_greedy`, calling `progress` with the number of input bytes consumed
+/// each time roughly another `PROGRESS_INTERVAL` bytes have been encoded.
+fn compress_greedy_with_progress<S: Source, O: Sink, F: FnMut(usize)>(
+    data: &S,
+    size: Size,
+    out: &mut O,
+    mut progress: F,
+) -> Result<(), CompressionError> {
+    let width = size.unit_len();
+    let max_len = data.byte_len();
+    let start_len = out.written();
+
+    // The longest literal is one unit longer than the longest run count
+    let longest_literal = max_literal(size);
+    let longest_run_count = longest_literal - 1;
+
+    let literal = |out: &mut O, start: usize, len: usize| {
+        push_count(out, width, -(len as i64));
+        data.extend(out, start, len);
+        if out.written() - start_len > max_len {
+            return Err(CompressionError::EncoderFailed);
+        }
+        Ok(())
+    };
+
+    let mut remaining = max_len / width;
+    let mut pos = 0;
+    let mut start = 0;
+    let mut unmatched = 0;
+    let mut run_target = 2;
+    let mut next_report = PROGRESS_INTERVAL / width;
+
+    while remaining > 0 {
+        if pos >= next_report {
+            progress(pos * width);
+            next_report = pos + PROGRESS_INTERVAL / width;
+        }
+
+        // Look for a run of at least `run_target` units, then see how far it continues.
+        // While a literal is pending, a run needs to be at least 3 units long to be
+        // worth splitting the literal for.
+        let found_run = remaining >= run_target
+            && data.eq(pos, pos + 1)
+            && (run_target == 2 || data.eq(pos, pos + 2));
+
+        if found_run {
+            remaining -= run_target;
+            let mut len = run_target;
+            run_target = 2;
+            let extra =
+                data.count_equal(pos, pos + len, remaining.min(longest_run_count + 2 - len));
+            len += extra;
+            remaining -= extra;
+
+            if start != pos {
+                literal(out, start, unmatched)?;
+                unmatched = 0;
+            }
+            push_count(out, width, len as i64 - 2);
+            out.extend_from_slice(&data.unit(pos)[..width]);
+            if out.written() - start_len > max_len {
+                return Err(CompressionError::EncoderFailed);
+            }
+
+            pos += len;
+            start = pos;
+        } else {
+            remaining -= 1;
+            pos += 1;
+            unmatched += 1;
+
+            // Flush the literal once it's as long as a single command can hold
+            if unmatched == longest_literal {
+                literal(out, start, unmatched)?;
+                start = pos;
+                unmatched = 0;
+                run_target = 2;
+            } else if unmatched == longest_literal - 1 {
+                run_target = 2;
+            } else {
+                run_target = 3;
+            }
+        }
+    }
+
+    if start != pos {
+        literal(out, start, unmatched)?;
+    }
+    Ok(())
+}
+
+/// `compress_greedy`'s command choices, made from the lengths of the runs of
+/// identical units in its input rather than by comparing units itself, so that
+/// one scan of the input can drive an encoder for each `Size` at once.
+///
+/// `compress_greedy` only ever asks whether neighbouring units are equal, so
+/// fed the input split into maximal runs, in order, this writes exactly the
+/// same output and fails in exactly the same cases.
+pub(crate) struct RunEncoder<O: Sink> {
+    size: Size,
+    out: O,
+    /// The unit at which the pending literal begins
+    start: usize,
+    /// The number of units in the pending literal
+    unmatched: usize,
+    /// The length of the input, past which the output counts as expanded
+    max_len: usize,
+}
+
+impl<O: Sink> RunEncoder<O> {
+    /// Creates an encoder for `max_len` bytes of input in increments of `size`.
+    pub(crate) fn new(size: Size, max_len: usize, out: O) -> RunEncoder<O> {
+        RunEncoder {
+            size,
+            out,
+            start: 0,
+            unmatched: 0,
+            max_len,
+        }
+    }
+
+    /// Whether the output has already grown larger than the input, so that
+    /// `finish` is bound to fail.
+    pub(crate) fn expanded(&self) -> bool {
+        self.out.written() > self.max_len
+    }
+
+    /// How many identical units it takes to start a run, as `compress_greedy`
+    /// tracks in `run_target`: 2 when there's no literal to interrupt, or when
+    /// one more unit would fill it, and 3 otherwise.
+    fn run_target(&self) -> usize {
+        if self.unmatched == 0 || self.unmatched == max_literal(self.size) - 1 {
+            2
+        } else {
+            3
+        }
+    }
+
+    fn flush_literal(&mut self, data: &[u8]) {
+        let width = self.size.unit_len();
+        push_count(&mut self.out, width, -(self.unmatched as i64));
+        self.out
+            .extend_from_slice(&data[self.start * width..(self.start + self.unmatched) * width]);
+        self.unmatched = 0;
+    }
+
+    /// Adds the next `count` units to the pending literal, each of which
+    /// differs from the units on either side of it, writing the literal out
+    /// each time it's as long as a single command can hold.
+    #[inline]
+    pub(crate) fn push_literal(&mut self, data: &[u8], mut count: usize) {
+        let longest_literal = max_literal(self.size);
+        while self.unmatched + count >= longest_literal {
+            count -= longest_literal - self.unmatched;
+            self.unmatched = longest_literal;
+            self.flush_literal(data);
+            self.start += longest_literal;
+        }
+        self.unmatched += count;
+    }
+
+    /// Encodes the maximal run of `len` identical units beginning at unit `pos`
+    /// of `data`, which must follow on from the previous run.
+    #[inline]
+    pub(crate) fn push_run(&mut self, data: &[u8], mut pos: usize, mut len: usize) {
+        let width = self.size.unit_len();
+        while len > 0 {
+            if len >= self.run_target() {
+                let run = len.min(max_run(self.size));
+                if self.unmatched > 0 {
+                    self.flush_literal(data);
+                }
+                push_count(&mut self.out, width, run as i64 - 2);
+                self.out
+                    .extend_from_slice(&data[pos * width..(pos + 1) * width]);
+                pos += run;
+                len -= run;
+                self.start = pos;
+            } else {
+                pos += 1;
+                len -= 1;
+                self.push_literal(data, 1);
+            }
+        }
+    }
+
+    /// Writes out the pending literal and returns the output, or
+    /// `CompressionError::EncoderFailed` if it's larger than the input.
+    pub(crate) fn finish(mut self, data: &[u8]) -> Result<O, CompressionError> {
+        if self.unmatched > 0 {
+            self.flush_literal(data);
+        }
+        if self.expanded() {
+            return Err(CompressionError::EncoderFailed);
+        }
+        Ok(self.out)
+    }
+}
+
+/// Compresses `data` in increments of `size` into `out`, returning the number
+/// of bytes written, without allocating.
+///
+/// This uses the Rust port of the C encoder, so the output is identical to
+/// `compress`'s, and fails the same way on data which would expand. If the
+/// output doesn't fit in `out`, `CompressionError::ScratchTooSmall` gives the
+/// length it needs; `out` is left holding as much of it as fit. An `out` of
+/// `max_compressed_len(data.len(), size)` bytes is always large enough.
+pub fn compress_in(data: &[u8], size: Size, out: &mut [u8]) -> Result<usize, CompressionError> {
+    check_alignment(data.len(), size)?;
+    let capacity = out.len();
+    let mut sink = SliceSink {
+        buf: out,
+        written: 0,
+    };
+    compress_greedy(&Contiguous::new(data, size), size, &mut sink)?;
+    if sink.written > capacity {
+        return Err(CompressionError::ScratchTooSmall {
+            needed: sink.written,
+        });
+    }
+    Ok(sink.written)
+}
+
+/// Compresses several buffers in increments of `size` as though they were
+/// one contiguous buffer, without copying them together first.
+///
+/// This is intended for the two halves of a ring buffer, as returned by
+/// `VecDeque::as_slices`. Runs are detected across the boundaries between
+/// segments, and individual units may straddle a boundary, so the output is
+/// identical to compressing the concatenation of the segments with `compress`.
+/// Only the combined length needs to be a multiple of `size`.
+pub fn compress_segments(segments: &[&[u8]], size: Size) -> Result<Vec<u8>, CompressionError> {
+    let source = Segments::new(segments, size);
+    check_alignment(source.byte_len(), size)?;
+    let mut out = Vec::new();
+    compress_greedy(&source, size, &mut out)?;
+    Ok(out)
+}
+
+/// Compresses `data` in increments of `size`, calling `f(processed, total)`
+/// as compression progresses, where both are counts of input bytes.
+///
+/// `f` is called with `processed` of 0 before compression starts, after
+/// roughly every 64 KiB of input, and with `processed` equal to `total` once
+/// compression has succeeded; it isn't called again after a failure.
+/// Compression is done by a Rust port of the C encoder so that progress can
+/// be reported along the way, and the output is identical to `compress`'s.
+pub fn compress_with_progress<F: FnMut(usize, usize)>(
+    data: &[u8],
+    size: Size,
+    mut f: F,
+) -> Result<Vec<u8>, CompressionError> {
+    check_alignment(data.len(), size)?;
+    let total = data.len();
+    f(0, total);
+    let mut out = Vec::new();
+    compress_greedy_with_progress(&Contiguous::new(data, size), size, &mut out, |processed| {
+        f(processed, total)
+    })?;
+    f(total, total);
+    Ok(out)
+}
+
+/// A reusable compressor which keeps its output buffer between calls.
+///
+/// The C encoder allocates a fresh output buffer on every call and offers no
+/// way to write into a caller-provided one, so `Compressor` uses a Rust port
+/// of the same algorithm instead. Its output is identical to `compress`'s, but
+/// once its buffer has grown to fit the largest output, compressing doesn't
+/// allocate at all - which matters when compressing many small blocks.
+#[derive(Clone, Debug, Default)]
+pub struct Compressor {
+    buf: Vec<u8>,
+}
+
+impl Compressor {
+    /// Creates a compressor with an empty buffer.
+    pub fn new() -> Compressor {
+        Compressor { buf: Vec::new() }
+    }
+
+    /// Creates a compressor whose buffer can hold `capacity` bytes of output
+    /// before needing to grow.
+    pub fn with_capacity(capacity: usize) -> Compressor {
+        Compressor {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Compresses `data` in increments of `size`, as with `compress`.
+    ///
+    /// The returned slice borrows the compressor's buffer, and is overwritten
+    /// by the next call.
+    pub fn compress(&mut self, data: &[u8], size: Size) -> Result<&[u8], CompressionError> {
+        check_alignment(data.len(), size)?;
+        self.buf.clear();
+        compress_greedy(&Contiguous::new(data, size), size, &mut self.buf)?;
+        Ok(&self.buf)
+    }
+}
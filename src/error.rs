@@ -0,0 +1,183 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::HeaderWidth;
+
+/// The ways compressing or decompressing can fail.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CompressionError {
+    /// The input isn't an even multiple of the requested `Size`: it's `remainder`
+    /// bytes longer than the nearest multiple of `width`, the unit length in bytes.
+    Misaligned { width: usize, remainder: usize },
+    /// The input is too large to be passed to the C encoder.
+    InputTooLarge,
+    /// The C encoder reported a failure, or produced no output for non-empty input.
+    EncoderFailed,
+    /// Compressed data ended partway through a command.
+    Truncated,
+    /// A header is too short, or doesn't begin with a zero byte.
+    InvalidHeader,
+    /// A header's size byte doesn't name any `Size`.
+    UnknownSizeByte(u8),
+    /// Compressed data decompressed to a different size than its header declares.
+    SizeMismatch { expected: usize, actual: usize },
+    /// An I/O error occurred while reading input or writing output.
+    Io(io::Error),
+    /// Compressed data came out larger than the space allowed for it.
+    BudgetExceeded { actual: usize, budget: usize },
+    /// The C encoder reported an output size which is negative or larger than
+    /// its input, which it never legitimately produces.
+    EncoderInconsistentSize,
+    /// Two buffers which need to be the same length aren't.
+    LengthMismatch { expected: usize, actual: usize },
+    /// A block is followed by this many bytes which aren't part of it.
+    TrailingData(usize),
+    /// An archive's directory is malformed, or an entry can't be stored in one.
+    InvalidArchive,
+    /// A literal command wasn't followed by the marker byte set in
+    /// `CompressOptions::literal_marker`; holds the byte found instead.
+    UnexpectedLiteralMarker(u8),
+    /// Data doesn't begin with the signature passed to `strip_signature`.
+    SignatureMismatch,
+    /// A payload of `len` bytes can't hold the size its header declares, which
+    /// needs between `min` and `max` bytes.
+    ImplausiblePayloadLen { len: usize, min: usize, max: usize },
+    /// A payload has more commands than the decoder it's meant for can handle.
+    TooManyCommands { commands: usize, limit: usize },
+    /// A rectangle passed to `compress_rect` doesn't fit within its image.
+    RegionOutOfBounds,
+    /// `HeaderBuilder` was asked for a header width which can't correctly hold
+    /// the size it declares.
+    UnsupportedHeaderWidth {
+        decompressed_size: u32,
+        width: HeaderWidth,
+    },
+    /// The buffer passed to `compress_in` is too small; the output needs `needed` bytes.
+    ScratchTooSmall { needed: usize },
+    /// An asset's framing is missing, malformed, or disagrees with its CMP header.
+    InvalidAsset,
+    /// A frame of a sequence has an unknown tag, or is a delta frame with no
+    /// frame before it.
+    InvalidSequence,
+    /// Blocks joined by `concat_blocks` wouldn't be split back apart at the
+    /// same place when read by `BlockIter`.
+    AmbiguousBlockBoundary,
+    /// Decompressing would produce more than the `limit` bytes allowed by
+    /// `CompressOptions::max_output`.
+    OutputLimitExceeded { limit: usize },
+}
+
+impl Error for CompressionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CompressionError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompressionError::Misaligned { width, remainder } => write!(
+                f,
+                "Provided buffer is not an even multiple of {} bits ({} bytes over)",
+                width * 8,
+                remainder
+            ),
+            CompressionError::InputTooLarge => {
+                write!(f, "Provided buffer is too large to compress")
+            }
+            CompressionError::EncoderFailed => write!(f, "Unable to compress data!"),
+            CompressionError::Truncated => {
+                write!(f, "Compressed data ended in the middle of a command")
+            }
+            CompressionError::InvalidHeader => write!(f, "Invalid CMP header"),
+            CompressionError::UnknownSizeByte(byte) => {
+                write!(f, "Header has an unknown size byte ({:#04X})", byte)
+            }
+            CompressionError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Compressed data doesn't match the size declared in its header \
+                 (expected {} bytes, got {})",
+                expected, actual
+            ),
+            CompressionError::Io(ref e) => write!(f, "I/O error: {}", e),
+            CompressionError::BudgetExceeded { actual, budget } => write!(
+                f,
+                "Compressed data exceeds its size budget ({} bytes, budget {})",
+                actual, budget
+            ),
+            CompressionError::EncoderInconsistentSize => {
+                write!(f, "Encoder reported an impossible compressed size")
+            }
+            CompressionError::LengthMismatch { expected, actual } => write!(
+                f,
+                "Buffer lengths don't match (expected {} bytes, got {})",
+                expected, actual
+            ),
+            CompressionError::TrailingData(len) => {
+                write!(f, "Block is followed by {} bytes of trailing data", len)
+            }
+            CompressionError::InvalidArchive => write!(f, "Invalid CMP archive"),
+            CompressionError::UnexpectedLiteralMarker(byte) => {
+                write!(
+                    f,
+                    "Literal command has the wrong marker byte ({:#04X})",
+                    byte
+                )
+            }
+            CompressionError::SignatureMismatch => {
+                write!(f, "Data doesn't begin with the expected signature")
+            }
+            CompressionError::ImplausiblePayloadLen { len, min, max } => write!(
+                f,
+                "Payload of {} bytes can't match its header (expected {} to {} bytes)",
+                len, min, max
+            ),
+            CompressionError::TooManyCommands { commands, limit } => write!(
+                f,
+                "Compressed data has too many commands ({}, limit {})",
+                commands, limit
+            ),
+            CompressionError::RegionOutOfBounds => {
+                write!(f, "Region doesn't fit within the image")
+            }
+            CompressionError::UnsupportedHeaderWidth {
+                decompressed_size,
+                width,
+            } => write!(
+                f,
+                "A {}-bit header can't declare a size of {} bytes",
+                match width {
+                    HeaderWidth::Bits16 => 16,
+                    HeaderWidth::Bits32 => 32,
+                },
+                decompressed_size
+            ),
+            CompressionError::ScratchTooSmall { needed } => write!(
+                f,
+                "Output buffer is too small (compressed data needs {} bytes)",
+                needed
+            ),
+            CompressionError::InvalidAsset => write!(f, "Invalid CMP asset"),
+            CompressionError::InvalidSequence => write!(f, "Invalid frame sequence"),
+            CompressionError::AmbiguousBlockBoundary => {
+                write!(f, "Joined blocks can't be told apart when read back")
+            }
+            CompressionError::OutputLimitExceeded { limit } => write!(
+                f,
+                "Decompressed data exceeds the output limit of {} bytes",
+                limit
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for CompressionError {
+    fn from(error: io::Error) -> CompressionError {
+        CompressionError::Io(error)
+    }
+}
@@ -0,0 +1,75 @@
+//! Stand-ins for the C encoder's functions, used by the `pure-rust` feature.
+//!
+//! These have the same signatures and contract as the functions in
+//! `compress_rtns.h` - including returning a `malloc`ed buffer - so that the
+//! rest of the crate works the same whichever encoder it's built with. They're
+//! implemented with the Rust port of the C encoder, which produces identical
+//! output.
+
+use std::ptr;
+use std::slice;
+
+use libc::c_int;
+
+use crate::encode::{compress_greedy, Contiguous};
+use crate::Size;
+
+/// Compresses `units` units of `size` at `data`, storing a `malloc`ed buffer
+/// holding the output in `data_out` and its length in `compressed_size`.
+///
+/// Returns 0 on success, or -1 if the output would be larger than the input.
+unsafe fn cmpr(
+    size: Size,
+    data: *const u8,
+    units: c_int,
+    data_out: *mut *mut u8,
+    compressed_size: *mut c_int,
+) -> c_int {
+    let data = slice::from_raw_parts(data, units as usize * size.unit_len());
+    let mut out = Vec::new();
+    if compress_greedy(&Contiguous::new(data, size), size, &mut out).is_err() {
+        return -1;
+    }
+    // `malloc(0)` may return null, which callers treat as a failure
+    let buf = libc::malloc(out.len().max(1)) as *mut u8;
+    if buf.is_null() {
+        return -1;
+    }
+    ptr::copy_nonoverlapping(out.as_ptr(), buf, out.len());
+    *data_out = buf;
+    *compressed_size = out.len() as c_int;
+    0
+}
+
+pub(crate) unsafe fn cmpr_8bit(
+    data_stream: *const u8,
+    length: c_int,
+    data_out: *mut *mut u8,
+    compressed_size: *mut c_int,
+) -> c_int {
+    cmpr(Size::Byte, data_stream, length, data_out, compressed_size)
+}
+
+pub(crate) unsafe fn cmpr_16bit(
+    data_stream: *const u8,
+    length: c_int,
+    data_out: *mut *mut u8,
+    compressed_size: *mut c_int,
+) -> c_int {
+    cmpr(Size::Word, data_stream, length, data_out, compressed_size)
+}
+
+pub(crate) unsafe fn cmpr_32bit(
+    data_stream: *const u8,
+    length: c_int,
+    data_out: *mut *mut u8,
+    compressed_size: *mut c_int,
+) -> c_int {
+    cmpr(
+        Size::Longword,
+        data_stream,
+        length,
+        data_out,
+        compressed_size,
+    )
+}
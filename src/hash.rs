@@ -0,0 +1,89 @@
+//! Hashing uncompressed data alongside compressing it.
+
+use std::io::{ErrorKind, Read};
+
+use crate::{compress, CompressionError, Size};
+
+/// The CRC-32 lookup table for the reflected IEEE polynomial, built at compile time.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues a CRC-32 over `data`, starting from a `crc` returned by a
+/// previous call, or 0 to begin a new one.
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// The CRC-32 of `data`, as computed by zlib's `crc32` and PNG.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// How much `compress_reader_with_hash` asks its reader for at a time.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Compresses `data` in increments of `size`, as with `compress`, also
+/// returning the CRC-32 of the uncompressed `data`.
+///
+/// The hash is the standard CRC-32 used by zlib, gzip and PNG (the reflected
+/// IEEE 802.3 polynomial `0xEDB88320`, with an initial value and final XOR of
+/// `0xFFFFFFFF`), so it can be checked with any CRC-32 implementation; it won't
+/// change in future versions. The C encoder takes its whole input in one
+/// call, so the hash is computed in a pass of its own just before compressing.
+pub fn compress_with_hash(data: &[u8], size: Size) -> Result<(Vec<u8>, u32), CompressionError> {
+    let hash = crc32(data);
+    Ok((compress(data, size)?, hash))
+}
+
+/// Reads `reader` to the end and compresses its contents in increments of
+/// `size`, also returning the CRC-32 of the uncompressed contents.
+///
+/// The hash is the same one `compress_with_hash` returns. `reader` is only read
+/// once: each chunk is hashed as it arrives, then kept. As with
+/// `compress_reader`, the C encoder needs its entire input at once, so the full
+/// contents of `reader` are buffered in memory before compression begins; peak
+/// memory use is the size of the input plus the size of the compressed output.
+pub fn compress_reader_with_hash<R: Read>(
+    mut reader: R,
+    size: Size,
+) -> Result<(Vec<u8>, u32), CompressionError> {
+    let mut data = Vec::new();
+    let mut hash = 0;
+    loop {
+        let start = data.len();
+        data.resize(start + READ_CHUNK, 0);
+        let read = match reader.read(&mut data[start..]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {
+                data.truncate(start);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        data.truncate(start + read);
+        if read == 0 {
+            break;
+        }
+        hash = crc32_update(hash, &data[start..]);
+    }
+    Ok((compress(&data, size)?, hash))
+}
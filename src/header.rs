@@ -0,0 +1,311 @@
+//! Reading and writing the header which precedes a CMP stream.
+
+use crate::{CompressionError, Size};
+
+/// The width of the size field in a CMP header.
+///
+/// Sizes up to 65535 bytes are stored in a 16-bit field; anything larger
+/// is stored in a 32-bit field preceded by a word of padding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HeaderWidth {
+    /// 4-byte header with a 16-bit size field
+    Bits16,
+    /// 8-byte header with a 32-bit size field
+    Bits32,
+}
+
+/// A parsed CMP header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// The width of data the stream was compressed with.
+    pub size: Size,
+    /// The size of the data once decompressed, in bytes.
+    pub decompressed_size: u32,
+    /// Whether the size was stored in a 16-bit or 32-bit field.
+    pub width: HeaderWidth,
+}
+
+impl Header {
+    /// The number of bytes this header occupies at the start of a stream.
+    pub fn encoded_len(&self) -> usize {
+        match self.width {
+            HeaderWidth::Bits16 => 4,
+            HeaderWidth::Bits32 => 8,
+        }
+    }
+}
+
+/// The first byte of every CMP header.
+///
+/// The first word of a header is read by the decoder as a 16-bit value whose
+/// low byte holds the data width; the high byte is unused and always zero in
+/// streams written by Sega's tool. `parse_header` rejects headers which don't
+/// begin with it.
+///
+/// This byte isn't safe to repurpose for metadata. Because the decoder reads
+/// the whole word rather than just its low byte, a decoder which compares that
+/// word against the known width values - rather than masking off the high
+/// byte first - would misread a stream with anything else here, and there's no
+/// way to tell from the outside which kind a given game uses. Containers which
+/// need to store something with each block, such as an asset type, should keep
+/// it outside the header, as `Archive` does with entry names.
+pub const HEADER_LEADING_BYTE: u8 = 0x00;
+
+/// The largest size which fits in a 16-bit header.
+const MAX_16BIT_SIZE: u32 = 65535;
+
+/// Returns the length of the header `create_header` writes for data of
+/// `decompressed_size` bytes: 4 for sizes which fit in 16 bits, and 8 otherwise.
+pub fn header_len_for(decompressed_size: u32) -> usize {
+    if decompressed_size > MAX_16BIT_SIZE {
+        8
+    } else {
+        4
+    }
+}
+
+/// Returns the largest decompressed size, in bytes, a header of `width` can declare.
+///
+/// That's 65535 for `Bits16` and 4294967295 for `Bits32`. `create_header` takes
+/// its size as an `i32`, so it can only reach 2147483647 with a 32-bit header;
+/// `HeaderBuilder` can write the full range.
+/// A 32-bit header is only recognized by `parse_header` for sizes above 65535,
+/// so smaller sizes always use a 16-bit one.
+pub fn max_size_for_width(width: HeaderWidth) -> u32 {
+    match width {
+        HeaderWidth::Bits16 => MAX_16BIT_SIZE,
+        HeaderWidth::Bits32 => u32::MAX,
+    }
+}
+
+fn size_byte(compression_type: Size) -> u8 {
+    match compression_type {
+        Size::Byte => 0x0,
+        Size::Word => 0x4,
+        Size::Longword => 0xC,
+    }
+}
+
+/// The byte order used for the size field of a header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// Big endian, as used by the Saturn.
+    #[default]
+    Big,
+    /// Little endian. Saturn games won't be able to read headers written this way.
+    Little,
+}
+
+/// Writes a CMP header; this header is expected to come at the beginning of a compressed CMP stream.
+///
+/// `decompressed_size` is the size of the slice passed to `compress`,
+/// while `compression_type` is the same value passed to `compress`.
+pub fn create_header(decompressed_size: i32, compression_type: Size) -> Vec<u8> {
+    create_header_with_endianness(decompressed_size, compression_type, Endianness::Big)
+}
+
+/// Writes a CMP header as with `create_header`, writing the size field in
+/// the specified byte order.
+///
+/// Only `Endianness::Big` produces a header the Saturn can read; `Little` is
+/// intended for containers on other platforms which reuse the CMP header layout.
+pub fn create_header_with_endianness(
+    decompressed_size: i32,
+    compression_type: Size,
+    endianness: Endianness,
+) -> Vec<u8> {
+    // Negative sizes have always been truncated into a 16-bit header
+    let size = if decompressed_size > MAX_16BIT_SIZE as i32 {
+        decompressed_size as u32
+    } else {
+        decompressed_size as u16 as u32
+    };
+    HeaderBuilder::new(compression_type)
+        .decompressed_size(size)
+        .endianness(endianness)
+        .build()
+        .expect("an automatically chosen header width always fits its size")
+}
+
+/// Builds a CMP header with more control over its layout than `create_header`.
+///
+/// Each setter is optional: by default the header declares a size of 0, is
+/// written big endian, has no signature, and uses a 16-bit size field for
+/// sizes up to 65535 bytes and a 32-bit one otherwise, just like
+/// `create_header`. Combinations which can't be read back are rejected by
+/// `build`.
+///
+/// ```
+/// use sega_cmp::{create_header, HeaderBuilder, HeaderWidth, Size};
+///
+/// let header = HeaderBuilder::new(Size::Word)
+///     .decompressed_size(512)
+///     .build()
+///     .unwrap();
+/// assert_eq!(header, create_header(512, Size::Word));
+///
+/// // A 32-bit size field can't hold a size which would fit in 16 bits
+/// let forced = HeaderBuilder::new(Size::Word)
+///     .decompressed_size(512)
+///     .width(HeaderWidth::Bits32)
+///     .build();
+/// assert!(forced.is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderBuilder {
+    size: Size,
+    decompressed_size: u32,
+    width: Option<HeaderWidth>,
+    endianness: Endianness,
+    signature: Vec<u8>,
+}
+
+impl HeaderBuilder {
+    /// Starts a header for data compressed in increments of `size`.
+    pub fn new(size: Size) -> HeaderBuilder {
+        HeaderBuilder {
+            size,
+            decompressed_size: 0,
+            width: None,
+            endianness: Endianness::Big,
+            signature: Vec::new(),
+        }
+    }
+
+    /// Sets the size of the data once decompressed, in bytes.
+    pub fn decompressed_size(mut self, decompressed_size: u32) -> HeaderBuilder {
+        self.decompressed_size = decompressed_size;
+        self
+    }
+
+    /// Forces the width of the size field, rather than choosing it from the size.
+    ///
+    /// `parse_header` only recognizes a 32-bit field holding a size which
+    /// wouldn't have fit in 16 bits, so apart from `Bits16` for sizes up to
+    /// 65535 and `Bits32` for larger ones, `build` rejects the header.
+    pub fn width(mut self, width: HeaderWidth) -> HeaderBuilder {
+        self.width = Some(width);
+        self
+    }
+
+    /// Sets the byte order of the size field, as with `create_header_with_endianness`.
+    pub fn endianness(mut self, endianness: Endianness) -> HeaderBuilder {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Prefixes the header with `signature`, as with `compress_with_signature`.
+    ///
+    /// **A stock decoder can't read the result** until `strip_signature` removes it.
+    pub fn signature(mut self, signature: &[u8]) -> HeaderBuilder {
+        self.signature = signature.to_vec();
+        self
+    }
+
+    /// Writes the header, preceded by its signature if it has one.
+    ///
+    /// Returns `CompressionError::UnsupportedHeaderWidth` if a width was
+    /// forced which can't correctly hold the size.
+    pub fn build(&self) -> Result<Vec<u8>, CompressionError> {
+        let fits_16 = self.decompressed_size <= MAX_16BIT_SIZE;
+        let width = match self.width {
+            None if fits_16 => HeaderWidth::Bits16,
+            None => HeaderWidth::Bits32,
+            Some(width) => {
+                if (width == HeaderWidth::Bits16) != fits_16 {
+                    return Err(CompressionError::UnsupportedHeaderWidth {
+                        decompressed_size: self.decompressed_size,
+                        width,
+                    });
+                }
+                width
+            }
+        };
+
+        let mut header = self.signature.clone();
+        // First word is always the size indicator
+        header.extend_from_slice(&[HEADER_LEADING_BYTE, size_byte(self.size)]);
+        match width {
+            HeaderWidth::Bits16 => {
+                let size = self.decompressed_size as u16;
+                header.extend_from_slice(&match self.endianness {
+                    Endianness::Big => size.to_be_bytes(),
+                    Endianness::Little => size.to_le_bytes(),
+                });
+            }
+            HeaderWidth::Bits32 => {
+                // One word of padding, then the size as 32-bit
+                header.extend_from_slice(&[0, 0]);
+                let size = self.decompressed_size;
+                header.extend_from_slice(&match self.endianness {
+                    Endianness::Big => size.to_be_bytes(),
+                    Endianness::Little => size.to_le_bytes(),
+                });
+            }
+        }
+        // Callers reserve space for a header using `header_len_for`
+        debug_assert_eq!(
+            header.len() - self.signature.len(),
+            header_len_for(self.decompressed_size)
+        );
+        Ok(header)
+    }
+}
+
+/// Reads the CMP header at the start of `data`.
+///
+/// The compressed payload begins `encoded_len()` bytes into `data`.
+/// A 32-bit header is recognized by an empty 16-bit size field followed by
+/// a 32-bit size too large to have fit in 16 bits; anything else is read as
+/// a 16-bit header.
+///
+/// That means a header declaring a size of 0 is ambiguous when `data` continues
+/// past it: if the next 4 bytes hold another Word or Longword header, they're
+/// read as a 32-bit size. `BlockIter`, which walks back-to-back blocks, resolves
+/// this by checking which reading decodes.
+pub fn parse_header(data: &[u8]) -> Result<Header, CompressionError> {
+    if data.len() < 4 || data[0] != HEADER_LEADING_BYTE {
+        return Err(CompressionError::InvalidHeader);
+    }
+
+    let size = match data[1] {
+        0x0 => Size::Byte,
+        0x4 => Size::Word,
+        0xC => Size::Longword,
+        byte => return Err(CompressionError::UnknownSizeByte(byte)),
+    };
+
+    let short_size = u16::from_be_bytes([data[2], data[3]]);
+    if short_size == 0 && data.len() >= 8 {
+        let long_size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        if long_size > MAX_16BIT_SIZE {
+            return Ok(Header {
+                size,
+                decompressed_size: long_size,
+                width: HeaderWidth::Bits32,
+            });
+        }
+    }
+
+    Ok(Header {
+        size,
+        decompressed_size: short_size as u32,
+        width: HeaderWidth::Bits16,
+    })
+}
+
+/// Returns the rest of `data` after `signature`, for CMP streams which a
+/// container prefixes with a signature identifying them, as written by
+/// `compress_with_signature`.
+///
+/// Returns `CompressionError::SignatureMismatch` if `data` doesn't begin with
+/// `signature`. Nothing in this crate looks for a signature unless asked to,
+/// so stock streams are never misread as having one; the result can be passed
+/// to `parse_header`, `decompress_with_header` or anything else which takes a
+/// header-prefixed stream.
+pub fn strip_signature<'a>(data: &'a [u8], signature: &[u8]) -> Result<&'a [u8], CompressionError> {
+    if !data.starts_with(signature) {
+        return Err(CompressionError::SignatureMismatch);
+    }
+    Ok(&data[signature.len()..])
+}
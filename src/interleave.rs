@@ -0,0 +1,74 @@
+//! Compressing interleaved data, such as bitplanes, one plane at a time.
+//!
+//! **This isn't a stock CMP format.** Graphics stored as interleaved planes
+//! put byte 0 of each plane side by side, then byte 1 of each, and so on, which
+//! breaks up runs that each plane has on its own. `compress_deinterleaved`
+//! reorders the data so that each plane's bytes are contiguous before
+//! compressing it: with `planes` planes, byte `i` of the input is byte
+//! `i / planes` of plane `i % planes`, and the planes are written one after
+//! another in order. The result is the plane count as a 32-bit big-endian
+//! integer, followed by the reordered data as a header-prefixed CMP stream.
+
+use std::convert::TryFrom;
+
+use crate::{compress_with_header, decompress_with_header, CompressionError, Size};
+
+/// The length of the plane count preceding the CMP stream.
+const PLANE_COUNT_LEN: usize = 4;
+
+/// Compresses `data`, made of `planes` interleaved planes, in increments of
+/// `size` after separating its planes, as described in the module documentation.
+///
+/// `data`'s length must be a multiple of `planes`, or
+/// `CompressionError::Misaligned` is returned, the same as when it isn't a
+/// multiple of `size`. A single plane compresses the data as-is.
+///
+/// # Panics
+///
+/// Panics if `planes` is 0.
+pub fn compress_deinterleaved(
+    data: &[u8],
+    planes: usize,
+    size: Size,
+) -> Result<Vec<u8>, CompressionError> {
+    assert!(planes > 0, "data must have at least one plane");
+    if !data.len().is_multiple_of(planes) {
+        return Err(CompressionError::Misaligned {
+            width: planes,
+            remainder: data.len() % planes,
+        });
+    }
+    let count = u32::try_from(planes).map_err(|_| CompressionError::InputTooLarge)?;
+
+    let mut planar = Vec::with_capacity(data.len());
+    for plane in 0..planes {
+        planar.extend(data.iter().skip(plane).step_by(planes));
+    }
+
+    let mut out = count.to_be_bytes().to_vec();
+    out.extend_from_slice(&compress_with_header(&planar, size)?);
+    Ok(out)
+}
+
+/// Decompresses data written by `compress_deinterleaved`, interleaving the
+/// planes again to restore the original data.
+///
+/// Returns `CompressionError::InvalidHeader` if the plane count is missing or
+/// zero, or the data doesn't divide evenly into that many planes.
+pub fn decompress_deinterleaved(blob: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    if blob.len() < PLANE_COUNT_LEN {
+        return Err(CompressionError::InvalidHeader);
+    }
+    let planes = u32::from_be_bytes([blob[0], blob[1], blob[2], blob[3]]) as usize;
+    let planar = decompress_with_header(&blob[PLANE_COUNT_LEN..])?;
+    if planes == 0 || !planar.len().is_multiple_of(planes) {
+        return Err(CompressionError::InvalidHeader);
+    }
+
+    let plane_len = planar.len() / planes;
+    let mut data = Vec::with_capacity(planar.len());
+    for i in 0..plane_len {
+        data.extend(planar.iter().skip(i).step_by(plane_len));
+    }
+    Ok(data)
+}
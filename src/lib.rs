@@ -9,7 +9,9 @@
 //!
 //! For example, imagine the text `AAAAAAAABBBBAABA`.
 //! If you described it in terms of patterns, you could think of it as being
-//! `8A4B2A1B1A` - which takes up 10 bytes instead of 16.
+//! `8A4B2A1B1A` - which takes up 10 bytes instead of 16. The `simple_rle`
+//! module implements exactly that scheme for experimenting with, though it
+//! isn't how CMP itself encodes data.
 //!
 //! Sega included CMP in the official Saturn SDK; they provided a closed-source
 //! commandline tool to compress data and source code to do compression from
@@ -22,20 +24,105 @@
 //! This crate provides two basic functions: the header-generating `create_header`,
 //! and the data-creating `compress`. Most Saturn games store both in the same place,
 //! with the header followed immedaitely by the compressed data.
+//!
+//! Going the other way, `decompress_with_header` decodes a payload preceded by
+//! its header, reading the width to decode it in from the header's size byte;
+//! it's the one to reach for whenever the header is available. `decompress`
+//! decodes a bare compressed payload, so it has to be told the width instead.
+//! `BlockIter` walks a series of header-prefixed payloads stored back to back.
+//! `CmpEncoder` and `CmpDecoder` wrap these in `Write` and `Read` adapters
+//! which mirror the API of `flate2`'s zlib types, `CmpCursor` reads and seeks
+//! within a stream's decompressed contents, and `Archive` bundles many named
+//! blocks into a single file.
 
-use std::error::Error;
-use std::fmt;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::mem;
 use std::ptr;
-use std::slice;
 
-use libc::{c_int, intptr_t};
+use libc::{c_int, c_void};
+
+mod analyze;
+mod archive;
+mod asset;
+mod buf;
+#[cfg(feature = "capi")]
+pub mod capi;
+mod compat;
+mod decode;
+mod delta;
+mod encode;
+mod error;
+#[cfg(feature = "pure-rust")]
+mod fallback;
+mod hash;
+mod header;
+mod interleave;
+#[cfg(feature = "opcodes")]
+pub mod opcodes;
+mod packed;
+pub mod simple_rle;
+mod split;
+mod stats;
+mod stream;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+#[cfg(feature = "text")]
+pub mod text;
+mod unit;
+mod verify;
+
+pub use crate::analyze::{
+    best_size, compare_modes, compress_all_modes, exact_sizes, export_run_stats, find_runs,
+    run_savings_table, suggest_size, ModeComparison, ModeResult, ModeStats, Run,
+};
+pub use crate::archive::Archive;
+pub use crate::asset::{from_saturn_asset, to_saturn_asset};
+pub use crate::buf::CompressedBuf;
+pub use crate::compat::{compatibility_info, Backend, CompatibilityInfo};
+pub use crate::decode::{
+    decompress, decompress_each, decompress_to, decompress_with_header, decompress_with_options,
+    decompressed_len, total_decompressed_size, BlockIter, ChunkedDecoder, CmpBlock,
+};
+pub use crate::delta::{compress_delta, compress_sequence, decompress_delta, decompress_sequence};
+pub use crate::encode::{
+    compress_in, compress_segments, compress_with_progress, max_compressed_len, CompressOptions,
+    Compressor, Effort,
+};
+pub use crate::error::CompressionError;
+pub use crate::hash::{compress_reader_with_hash, compress_with_hash};
+pub use crate::header::{
+    create_header, create_header_with_endianness, header_len_for, max_size_for_width, parse_header,
+    strip_signature, Endianness, Header, HeaderBuilder, HeaderWidth, HEADER_LEADING_BYTE,
+};
+pub use crate::interleave::{compress_deinterleaved, decompress_deinterleaved};
+pub use crate::packed::{
+    compress_block, compress_or_passthrough, concat_blocks, min_compressible_len, Compressed,
+    Packed,
+};
+pub use crate::split::compress_max_block;
+pub use crate::stats::{
+    command_histogram, compress_if_worthwhile, compress_palette, compress_with_stats,
+    estimate_decode_cost, report, CommandHistogram, CompressionStats, DecodeCost, FileReport,
+    PaletteStats,
+};
+pub use crate::stream::{CmpCursor, CmpDecoder, CmpEncoder};
+pub use crate::unit::{compress_units, Unit};
+pub use crate::verify::{
+    canonicalize, check_decoder_limits, check_payload_span, diagnose, diff_report,
+    first_difference, is_canonical, is_deterministic, is_position_independent, looks_like_cmp,
+    optimize, repair_header, DecodedCommand, Diagnostics, DiffReport,
+};
 
 /// Used to denote the width of data to compress.
 /// Because CMP compression was created to be used on the SH-2 CPU, the size names
 /// come from the three sizes of data used on the SH-2.
-#[derive(Copy, Clone)]
+///
+/// The default is `Byte`, since it places no alignment requirements on the data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
 pub enum Size {
     /// 8-bit
+    #[default]
     Byte,
     /// 16-bit
     Word,
@@ -43,150 +130,675 @@ pub enum Size {
     Longword,
 }
 
-#[derive(Debug)]
-pub struct CompressionError {
-    message: &'static str,
-}
-
-impl CompressionError {
-    fn new(message: &'static str) -> CompressionError {
-        return CompressionError { message: message };
+impl Size {
+    /// The number of bytes in a single unit of this size.
+    pub(crate) fn unit_len(self) -> usize {
+        match self {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Longword => 4,
+        }
     }
 }
 
-impl Error for CompressionError {
-    fn description(&self) -> &str {
-        return self.message;
-    }
+/// Returns the alignment, in bytes, which the Saturn's decoder needs for data
+/// compressed in increments of `size`.
+///
+/// The decoder reads each count and unit, and writes each decompressed unit, as
+/// a single access of `size`'s width. The SH-2 faults on word and longword
+/// accesses which aren't aligned to their width, so both the compressed data
+/// and the output buffer must start at an address which is a multiple of this
+/// value; the header is 4 or 8 bytes long, so it doesn't disturb the payload's
+/// alignment. Compressing on the host has no such requirement.
+pub fn decode_alignment(size: Size) -> usize {
+    size.unit_len()
 }
 
-impl fmt::Display for CompressionError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
-    }
-}
+#[cfg(feature = "pure-rust")]
+use crate::fallback::{cmpr_16bit, cmpr_32bit, cmpr_8bit};
 
+#[cfg(not(feature = "pure-rust"))]
 extern "C" {
     fn cmpr_8bit(
         data_stream: *const u8,
         length: c_int,
         data_out: *mut *mut u8,
-        compressed_size: *mut intptr_t,
+        compressed_size: *mut c_int,
     ) -> c_int;
     fn cmpr_16bit(
         data_stream: *const u8,
         length: c_int,
         data_out: *mut *mut u8,
-        compressed_size: *mut intptr_t,
+        compressed_size: *mut c_int,
     ) -> c_int;
     fn cmpr_32bit(
         data_stream: *const u8,
         length: c_int,
         data_out: *mut *mut u8,
-        compressed_size: *mut intptr_t,
+        compressed_size: *mut c_int,
     ) -> c_int;
 }
 
+// Errors are built out of line, in functions marked `#[cold]`, so the checks
+// which guard them stay small enough to inline into hot loops
+
+/// Builds the error for a `len` byte buffer which isn't a multiple of `size`.
+///
+/// Rejections are also logged as warnings through the `log` crate, which costs
+/// nothing unless a logger is installed.
+#[cold]
+fn misaligned(len: usize, size: Size) -> CompressionError {
+    log::warn!(
+        "Rejected {} byte buffer for {:?} compression: length must be a multiple of {}",
+        len,
+        size,
+        size.unit_len()
+    );
+    CompressionError::Misaligned {
+        width: size.unit_len(),
+        remainder: len % size.unit_len(),
+    }
+}
+
+#[cold]
+fn input_too_large() -> CompressionError {
+    CompressionError::InputTooLarge
+}
+
+#[cold]
+fn encoder_failed() -> CompressionError {
+    CompressionError::EncoderFailed
+}
+
+/// Returns an error if `len` bytes isn't an even multiple of `size`.
+#[inline]
+pub(crate) fn check_alignment(len: usize, size: Size) -> Result<(), CompressionError> {
+    if !len.is_multiple_of(size.unit_len()) {
+        return Err(misaligned(len, size));
+    }
+    Ok(())
+}
+
+/// Checks that `data` can be handed to the C encoder, returning the number of
+/// units of `size` it contains.
+///
+/// `data` must be an even multiple of `size`. The encoder takes a unit count,
+/// but works out byte offsets and sizes from it in `int`s, so the byte length
+/// must fit in a `c_int` as well as the unit count.
+#[inline]
+pub(crate) fn check_input(data: &[u8], size: Size) -> Result<c_int, CompressionError> {
+    check_alignment(data.len(), size)?;
+    if c_int::try_from(data.len()).is_err() {
+        return Err(input_too_large());
+    }
+    c_int::try_from(data.len() / size.unit_len()).map_err(|_| input_too_large())
+}
+
+/// The alignment the C encoder needs for its input in increments of `size`.
+fn input_alignment(size: Size) -> usize {
+    match size {
+        Size::Byte => 1,
+        Size::Word => mem::align_of::<i16>(),
+        Size::Longword => mem::align_of::<c_int>(),
+    }
+}
+
+/// Copies `data` into a buffer aligned for any `Size`.
+fn copy_aligned(data: &[u8]) -> Vec<u32> {
+    let mut buf = vec![0u32; data.len().div_ceil(4)];
+    unsafe { ptr::copy_nonoverlapping(data.as_ptr(), buf.as_mut_ptr() as *mut u8, data.len()) };
+    buf
+}
+
 /// Given a slice containing `u8`s, this function compresses the data in increments of `size`.
 /// On success, returns a slice containing the compressed data.
 ///
 /// When compressing in increments of word or longword, this function will return an error
-/// if the provided data isn't an even increment of that data type.
+/// if the provided data isn't an even increment of that data type. The slice itself
+/// may start at any address; if it isn't aligned for that data type, it's copied
+/// to an aligned buffer before being handed to the C encoder.
 /// Because this wraps a set of C functions, errors will be returned if the underlying
 /// functions return an error; information about why the error occurred may be available
 /// via stderr.
+///
+/// A memory-mapped file can be compressed directly by passing the slice its
+/// mapping derefs to. The C encoder only reads `data` while this call is
+/// running, and the borrow already prevents the mapping from being unmapped
+/// until it returns, so no wrapper type is needed for that. What no type can
+/// prevent is another process truncating or rewriting the file during the
+/// call, so only map files which nothing else will modify meanwhile.
+#[inline]
 pub fn compress(data: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
-    let mut out = ptr::null_mut();
-    let mut out_size: isize = 0;
+    compress_with_capacity(data, size, 0)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, returning a
+/// `Vec` with room for at least `capacity` bytes.
+///
+/// The encoder's output is copied into the returned `Vec` exactly once; when
+/// `capacity` is at least the compressed size, that copy is the `Vec`'s only
+/// allocation, and the spare room can be used to append further data without
+/// reallocating.
+pub fn compress_with_capacity(
+    data: &[u8],
+    size: Size,
+    capacity: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    let buf = compress_buf(data, size)?;
+    let mut out = Vec::with_capacity(capacity.max(buf.len()));
+    out.extend_from_slice(&buf);
+    Ok(out)
+}
+
+/// How much spare capacity `compress_with_capacity_policy` leaves in the
+/// `Vec` it returns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum CapacityPolicy {
+    /// No spare capacity, as returned by `compress`; best for output which is
+    /// kept around without being modified.
+    #[default]
+    Exact,
+    /// Room for this many more bytes after the compressed payload, such as for
+    /// appending a trailer without reallocating.
+    Reserve(usize),
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, allocating
+/// the returned `Vec` according to `policy`.
+///
+/// Unlike `compress_with_capacity`, which takes a total capacity to allocate
+/// before the compressed size is known, `CapacityPolicy::Reserve` is relative
+/// to the compressed size. As there, the encoder's output is copied exactly
+/// once, into a `Vec` allocated at its final capacity.
+pub fn compress_with_capacity_policy(
+    data: &[u8],
+    size: Size,
+    policy: CapacityPolicy,
+) -> Result<Vec<u8>, CompressionError> {
+    let buf = compress_buf(data, size)?;
+    let extra = match policy {
+        CapacityPolicy::Exact => 0,
+        CapacityPolicy::Reserve(extra) => extra,
+    };
+    let mut out = Vec::with_capacity(buf.len().saturating_add(extra));
+    out.extend_from_slice(&buf);
+    Ok(out)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, returning
+/// the encoder's own output buffer rather than a copy of it.
+pub fn compress_buf(data: &[u8], size: Size) -> Result<CompressedBuf, CompressionError> {
+    let units = check_input(data, size)?;
+
+    // The C encoder reads word and longword input through `short*` and `int*`
+    // pointers, which C requires to be aligned; a misaligned slice is copied
+    // into a suitably aligned buffer first
+    let aligned_copy;
+    let input = if (data.as_ptr() as usize).is_multiple_of(input_alignment(size)) {
+        data.as_ptr()
+    } else {
+        aligned_copy = copy_aligned(data);
+        aligned_copy.as_ptr() as *const u8
+    };
+
+    // The C functions store a pointer to their output in `out` as soon as
+    // they've allocated it, before they know whether the input will compress,
+    // so it may be non-null even when they fail
+    let mut out: *mut u8 = ptr::null_mut();
+    let mut out_size: c_int = 0;
 
     let result;
 
     match size {
         Size::Byte => {
             unsafe {
-                result = cmpr_8bit(
-                    data.as_ptr(),
-                    data.len() as c_int,
-                    &mut out as *mut _,
-                    &mut out_size,
-                );
+                result = cmpr_8bit(input, units, &mut out, &mut out_size);
             };
         }
         Size::Word => {
-            if data.len() % 2 != 0 {
-                return Err(CompressionError::new(
-                    "Provided buffer is not an even multiple of 16 bits",
-                ));
-            }
             unsafe {
-                result = cmpr_16bit(
-                    data.as_ptr(),
-                    data.len() as c_int / 2,
-                    &mut out as *mut _,
-                    &mut out_size,
-                );
+                result = cmpr_16bit(input, units, &mut out, &mut out_size);
             };
         }
         Size::Longword => {
-            if data.len() % 4 != 0 {
-                return Err(CompressionError::new(
-                    "Provided buffer is not an even multiple of 32 bits",
-                ));
-            }
             unsafe {
-                result = cmpr_32bit(
-                    data.as_ptr(),
-                    data.len() as c_int / 4,
-                    &mut out as *mut _,
-                    &mut out_size,
-                );
+                result = cmpr_32bit(input, units, &mut out, &mut out_size);
             };
         }
     }
 
-    let out_data;
-    unsafe { out_data = slice::from_raw_parts(out, out_size as usize) };
-
-    assert_eq!(out_size as c_int, out_data.len() as c_int);
-
     if result != 0 {
-        return Err(CompressionError::new("Unable to compress data!"));
+        if !out.is_null() {
+            unsafe { libc::free(out as *mut c_void) };
+        }
+        return Err(encoder_failed());
     }
 
-    let out_vec = Vec::from(out_data);
-    return Ok(out_vec);
+    // A zero return means `out` holds a buffer from `malloc`, with `out_size`
+    // bytes of it filled in, which is handed to a `CompressedBuf` to release.
+    if out.is_null() {
+        return Err(encoder_failed());
+    }
+    // The encoder fails rather than expanding its input, so a size outside
+    // that range can only be corrupt, and mustn't be used to build a slice
+    if out_size < 0 || out_size as usize > data.len() {
+        unsafe { libc::free(out as *mut c_void) };
+        return Err(CompressionError::EncoderInconsistentSize);
+    }
+    // Only empty input can legitimately compress to nothing; anything else
+    // means the encoder lost the data
+    if out_size == 0 && !data.is_empty() {
+        unsafe { libc::free(out as *mut c_void) };
+        return Err(encoder_failed());
+    }
+    Ok(unsafe { CompressedBuf::from_raw(out, out_size as usize) })
 }
 
-/// Writes a CMP header; this header is expected to come at the beginning of a compressed CMP stream.
+/// Compresses anything which can be viewed as bytes, such as a `Vec<u8>`,
+/// `Box<[u8]>` or `&str`, in increments of `size`.
 ///
-/// `decompressed_size` is the size of the slice passed to `compress`,
-/// while `compression_type` is the same value passed to `compress`.
-pub fn create_header(decompressed_size: i32, compression_type: Size) -> Vec<u8> {
-    let size_byte = match compression_type {
-        Size::Byte => 0x0,
-        Size::Word => 0x4,
-        Size::Longword => 0xC,
+/// This is identical to calling `compress` with `data.as_ref()`.
+#[inline]
+pub fn compress_any<T: AsRef<[u8]>>(data: T, size: Size) -> Result<Vec<u8>, CompressionError> {
+    compress(data.as_ref(), size)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, using the provided `options`.
+///
+/// With `Effort::Fast` this is identical to `compress`. With `Effort::Best`,
+/// compression is done by a slower Rust encoder which finds the smallest
+/// possible output; unlike the C encoder, it will still succeed if that
+/// output is larger than the input.
+///
+/// Setting `extended_runs` or `literal_marker` produces a non-stock stream which
+/// must be read with `decompress_with_options`, and `max_run` splits long runs;
+/// see `CompressOptions` for details.
+pub fn compress_with_options(
+    data: &[u8],
+    size: Size,
+    options: CompressOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut compressed = match options.effort {
+        Effort::Fast => compress(data, size)?,
+        Effort::Best => {
+            check_alignment(data.len(), size)?;
+            encode::compress_optimal(data, size)
+        }
     };
+    // `max_run` takes precedence over `extended_runs`
+    let extended_runs = options.extended_runs && options.max_run.is_none();
+    if extended_runs {
+        compressed = encode::extend_runs(&compressed, size);
+    }
+    // The format of the payload the stages below are given
+    let format = CompressOptions {
+        extended_runs,
+        literal_marker: None,
+        max_run: None,
+        ..options
+    };
+    if let Some(limit) = options.max_run {
+        compressed = encode::limit_runs(&compressed, size, format, limit);
+    }
+    if let Some(marker) = options.literal_marker {
+        compressed = encode::mark_literals(&compressed, size, format, marker);
+    }
+    Ok(compressed)
+}
 
-    // First word is always the size indicator
-    let mut header: Vec<u8> = vec![0, size_byte];
-
-    // 32-bit header if size is larger than 65535 bytes
-    if decompressed_size > 65535 {
-        // One word of padding
-        header.push(0);
-        header.push(0);
-        // Size as 32-bit big endian
-        header.push(((decompressed_size >> 24) & 0xFF) as u8);
-        header.push(((decompressed_size >> 16) & 0xFF) as u8);
-        header.push(((decompressed_size >> 8) & 0xFF) as u8);
-        header.push((decompressed_size & 0xFF) as u8);
-    // 16-bit header otherwise
-    } else {
-        // Size as 16-bit big endian
-        header.push(((decompressed_size as i16 >> 8) & 0xFF) as u8);
-        header.push((decompressed_size as i16 & 0xFF) as u8);
+/// Compresses the largest prefix of `data` which is an even multiple of
+/// `size`, returning the compressed prefix along with the remaining zero to
+/// three bytes, uncompressed, for the caller to deal with.
+///
+/// The compressed prefix is an ordinary stock payload, identical to calling
+/// `compress` on the prefix. `compress_with_tail` is a variant which stores
+/// the tail in the stream itself.
+pub fn compress_aligned_prefix(
+    data: &[u8],
+    size: Size,
+) -> Result<(Vec<u8>, &[u8]), CompressionError> {
+    let (aligned, tail) = data.split_at(data.len() - data.len() % size.unit_len());
+    Ok((compress(aligned, size)?, tail))
+}
+
+/// Compresses a `w` by `h` rectangle of the image in `data`, whose rows are
+/// `stride` bytes apart, in increments of `size`.
+///
+/// `x` and `w` are in bytes, and `y` and `h` in rows, so for an image with more
+/// than one byte per pixel they need to be multiplied by its pixel size. The
+/// rectangle is gathered row by row into one contiguous buffer and compressed
+/// as with `compress`, so it's `w * h` bytes, rather than each row, which
+/// needs to be a whole number of units. Returns `RegionOutOfBounds` if the
+/// rectangle runs past the end of a row or of `data`.
+pub fn compress_rect(
+    data: &[u8],
+    stride: usize,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    size: Size,
+) -> Result<Vec<u8>, CompressionError> {
+    // The end of the rectangle's last row, if its rows fit within `stride`
+    let end = x
+        .checked_add(w)
+        .filter(|&right| right <= stride)
+        .and_then(|right| match h {
+            0 => Some(0),
+            _ => (y.checked_add(h - 1)?)
+                .checked_mul(stride)?
+                .checked_add(right),
+        });
+    if end.is_none_or(|end| end > data.len()) {
+        return Err(CompressionError::RegionOutOfBounds);
+    }
+    if w == 0 || h == 0 {
+        return compress(&[], size);
+    }
+
+    let mut region = Vec::with_capacity(w * h);
+    for row in data.chunks(stride).skip(y).take(h) {
+        region.extend_from_slice(&row[x..x + w]);
+    }
+    compress(&region, size)
+}
+
+/// Compresses `data` in increments of `preferred`, falling back to narrower
+/// sizes if its length isn't a multiple of `preferred`, and returns the
+/// compressed payload along with the size it was actually compressed in.
+///
+/// The fallback order is `Longword`, then `Word`, then `Byte`, starting from
+/// `preferred`; since every length is a multiple of a byte, this only fails
+/// for reasons other than alignment, such as the encoder failing. The header
+/// needs to be written for the returned size, not `preferred`.
+pub fn compress_with_fallback(
+    data: &[u8],
+    preferred: Size,
+) -> Result<(Vec<u8>, Size), CompressionError> {
+    let mut size = preferred;
+    loop {
+        match compress(data, size) {
+            Err(CompressionError::Misaligned { .. }) if size != Size::Byte => {
+                size = match size {
+                    Size::Longword => Size::Word,
+                    _ => Size::Byte,
+                };
+            }
+            result => return result.map(|compressed| (compressed, size)),
+        }
     }
+}
+
+/// Compresses `data` in increments of `size`, allowing a length which isn't an
+/// even multiple of `size`.
+///
+/// The largest aligned prefix of `data` is compressed as with `compress`; the
+/// remaining one to three bytes are appended as a byte-mode literal command
+/// (a signed count of `-tail_len`, followed by the tail bytes). Returns the
+/// combined stream along with the length of the tail in bytes.
+///
+/// **This doesn't produce a stock CMP stream** - no Saturn decoder knows to switch
+/// to byte mode for the final command, so the result is only usable with a
+/// decoder which handles the tail itself. When the tail length is zero, the
+/// stream is identical to `compress`'s output.
+pub fn compress_with_tail(data: &[u8], size: Size) -> Result<(Vec<u8>, usize), CompressionError> {
+    let tail_len = data.len() % size.unit_len();
+    let (aligned, tail) = data.split_at(data.len() - tail_len);
+
+    let mut out = compress(aligned, size)?;
+    if tail_len > 0 {
+        out.push(-(tail_len as i8) as u8);
+        out.extend_from_slice(tail);
+    }
+    Ok((out, tail_len))
+}
 
-    return header;
+/// Compresses `data` in increments of `size`, as with `compress`, returning a
+/// boxed slice rather than a `Vec`.
+///
+/// This is useful when holding many compressed blobs which will never be modified.
+pub fn compress_boxed(data: &[u8], size: Size) -> Result<Box<[u8]>, CompressionError> {
+    compress(data, size).map(Vec::into_boxed_slice)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, returning
+/// the output as `bytes::Bytes`.
+///
+/// Requires the `bytes` feature. The encoder's output is copied straight into
+/// the `Bytes`, so this costs the same single copy as `compress`, rather than
+/// the two of calling `compress` and converting the `Vec` afterwards.
+#[cfg(feature = "bytes")]
+pub fn compress_bytes(data: &[u8], size: Size) -> Result<bytes::Bytes, CompressionError> {
+    let buf = compress_buf(data, size)?;
+    Ok(bytes::Bytes::copy_from_slice(&buf))
+}
+
+/// Reads `reader` to the end and compresses its contents in increments of `size`.
+///
+/// The C encoder needs its entire input at once, so the full contents of
+/// `reader` are buffered in memory before compression begins; peak memory use
+/// is the size of the input plus the size of the compressed output.
+pub fn compress_reader<R: Read>(mut reader: R, size: Size) -> Result<Vec<u8>, CompressionError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    compress(&data, size)
+}
+
+/// Consumes `bytes` and compresses them in increments of `size`, for data
+/// which is generated on the fly rather than already held in a buffer.
+///
+/// As with `compress_reader`, the C encoder needs its entire input at once, so
+/// every byte is collected into memory before compression begins, using the
+/// iterator's size hint to allocate up front. The collected length has to be a
+/// multiple of `size`, just as with `compress`.
+pub fn compress_iter<I: IntoIterator<Item = u8>>(
+    bytes: I,
+    size: Size,
+) -> Result<Vec<u8>, CompressionError> {
+    let data: Vec<u8> = bytes.into_iter().collect();
+    compress(&data, size)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, returning
+/// `CompressionError::BudgetExceeded` if the compressed payload is longer than
+/// `budget` bytes.
+pub fn compress_within(
+    data: &[u8],
+    size: Size,
+    budget: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    let compressed = compress(data, size)?;
+    if compressed.len() > budget {
+        return Err(CompressionError::BudgetExceeded {
+            actual: compressed.len(),
+            budget,
+        });
+    }
+    Ok(compressed)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, after
+/// checking that it's exactly `expected_len` bytes long.
+///
+/// Returns `CompressionError::LengthMismatch` without compressing anything if
+/// it isn't, which catches inputs which were only partly read before they
+/// turn into compressed data which is wrong but looks fine.
+pub fn compress_checked(
+    data: &[u8],
+    size: Size,
+    expected_len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    if data.len() != expected_len {
+        return Err(CompressionError::LengthMismatch {
+            expected: expected_len,
+            actual: data.len(),
+        });
+    }
+    compress(data, size)
+}
+
+/// Compresses `data` in increments of `size`, as with `compress`, also
+/// returning the number of commands in the compressed payload.
+pub fn compress_detailed(data: &[u8], size: Size) -> Result<(Vec<u8>, usize), CompressionError> {
+    let compressed = compress(data, size)?;
+    let commands = decode::Commands::new(&compressed, size).count();
+    Ok((compressed, commands))
+}
+
+/// Compresses `data` in increments of `size`, returning `signature` followed by
+/// the header written by `create_header` and the compressed payload.
+///
+/// This is for containers which identify CMP streams by a signature at the
+/// start of the file. **A stock decoder can't read the result** until the
+/// signature is removed again, which `strip_signature` does.
+pub fn compress_with_signature(
+    data: &[u8],
+    size: Size,
+    signature: &[u8],
+) -> Result<Vec<u8>, CompressionError> {
+    let blob = compress_with_header(data, size)?;
+    let mut out = Vec::with_capacity(signature.len() + blob.len());
+    out.extend_from_slice(signature);
+    out.extend_from_slice(&blob);
+    Ok(out)
+}
+
+/// Returns whether `a` and `b` compress to identical payloads in increments of `size`.
+///
+/// If the inputs are equal they're only compressed once. Fails if either input
+/// can't be compressed. Since a payload only decompresses one way, unequal
+/// inputs never compress identically; this is mainly a convenience for
+/// deduplicating assets which also checks that both can be compressed.
+pub fn compresses_identically(a: &[u8], b: &[u8], size: Size) -> Result<bool, CompressionError> {
+    if a == b {
+        compress_buf(a, size)?;
+        return Ok(true);
+    }
+    let (a, b) = (compress_buf(a, size)?, compress_buf(b, size)?);
+    Ok(*a == *b)
+}
+
+/// Compresses `data` in increments of `size`, returning the header written by
+/// `create_header` followed by the compressed payload.
+pub fn compress_with_header(data: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
+    let payload = compress(data, size)?;
+    let mut out = create_header(data.len() as i32, size);
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// Compresses `data` in increments of `size`, returning the header and payload
+/// padded with zero bytes to exactly `slot_len` bytes.
+///
+/// This suits games which give every asset a fixed-size slot. Decoders stop
+/// once they've produced the size declared in the header, so the padding is
+/// never read. If the header and payload together are already longer than
+/// `slot_len`, returns `CompressionError::BudgetExceeded` with their length.
+pub fn compress_padded_to(
+    data: &[u8],
+    size: Size,
+    slot_len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut out = compress_with_header(data, size)?;
+    if out.len() > slot_len {
+        return Err(CompressionError::BudgetExceeded {
+            actual: out.len(),
+            budget: slot_len,
+        });
+    }
+    out.resize(slot_len, 0);
+    Ok(out)
+}
+
+/// Compresses `data` in increments of `size`, returning the header and payload
+/// padded with zero bytes to a multiple of `align` bytes, along with their
+/// length before padding.
+///
+/// This suits games which DMA compressed data and need it to end on a word or
+/// longword boundary. As with `compress_padded_to`, decoders stop once they've
+/// produced the size declared in the header, so the padding is never read.
+/// Headers are 4 or 8 bytes and payloads are a whole number of units, so only
+/// an `align` wider than `size` ever needs padding.
+///
+/// # Panics
+///
+/// Panics if `align` is 0.
+pub fn compress_aligned_output(
+    data: &[u8],
+    size: Size,
+    align: usize,
+) -> Result<(Vec<u8>, usize), CompressionError> {
+    assert!(align > 0, "alignment must be at least 1 byte");
+    let mut out = compress_with_header(data, size)?;
+    let len = out.len();
+    out.resize(len.next_multiple_of(align), 0);
+    Ok((out, len))
+}
+
+/// Compresses `data` in increments of `size` and writes the header followed by
+/// the compressed payload to `w`, returning the total number of bytes written.
+///
+/// Errors from `w` are returned as `CompressionError::Io`.
+pub fn write_compressed<W: Write>(
+    w: &mut W,
+    data: &[u8],
+    size: Size,
+) -> Result<usize, CompressionError> {
+    let payload = compress(data, size)?;
+    let header = create_header(data.len() as i32, size);
+    w.write_all(&header)?;
+    w.write_all(&payload)?;
+    Ok(header.len() + payload.len())
+}
+
+/// Re-encodes a header-prefixed CMP stream in increments of `target`, with a
+/// fresh header.
+///
+/// The source stream's width is read from its header. As with `create_header`,
+/// the new header is 16-bit or 32-bit depending only on the decompressed size.
+pub fn transcode(blob: &[u8], target: Size) -> Result<Vec<u8>, CompressionError> {
+    let data = decompress_with_header(blob)?;
+    compress_with_header(&data, target)
+}
+
+/// Compresses one fragment of data which will be decompressed and concatenated
+/// with the fragments after it at runtime, letting a run which crosses into
+/// the next fragment be encoded once instead of being split in two.
+///
+/// CMP commands can't be left open across streams, so instead any trailing
+/// units of `data` which repeat `next_first_unit` - the first unit of the
+/// following fragment - are left out of this fragment's payload. Returns the
+/// compressed payload along with the number of bytes left out; the caller must
+/// prepend those bytes, `&data[data.len() - deferred..]`, to the next fragment
+/// before compressing it, and use `data.len() - deferred` as this fragment's
+/// decompressed size. With `next_first_unit` of `None`, nothing is deferred
+/// and this is identical to `compress`. A `next_first_unit` which isn't exactly
+/// one unit long is rejected with `CompressionError::LengthMismatch`.
+///
+/// If every unit of `data` repeats `next_first_unit`, all of it is deferred:
+/// the payload is empty, this fragment decompresses to nothing, and the whole
+/// of `data` moves into the next one.
+///
+/// The result is only valid if fragments are decompressed and joined in order.
+pub fn compress_continuation(
+    data: &[u8],
+    size: Size,
+    next_first_unit: Option<&[u8]>,
+) -> Result<(Vec<u8>, usize), CompressionError> {
+    check_alignment(data.len(), size)?;
+    let width = size.unit_len();
+    let deferred = match next_first_unit {
+        Some(unit) if unit.len() == width => {
+            data.rchunks_exact(width).take_while(|&u| u == unit).count() * width
+        }
+        Some(unit) => {
+            return Err(CompressionError::LengthMismatch {
+                expected: width,
+                actual: unit.len(),
+            })
+        }
+        None => 0,
+    };
+    let compressed = compress(&data[..data.len() - deferred], size)?;
+    Ok((compressed, deferred))
 }
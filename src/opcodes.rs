@@ -0,0 +1,35 @@
+//! The values which make up CMP commands, for writing decoders and
+//! disassemblers of your own.
+//!
+//! Enabled by the `opcodes` feature. CMP has no opcode bytes as such: every
+//! command begins with a signed, big-endian count the same width as the data,
+//! and the count's sign bit selects the command. With the sign bit clear, the
+//! command is a run, and the unit which follows is repeated `count +
+//! RUN_LENGTH_BIAS` times. With it set, the command is a literal, and the
+//! following `-count` units are copied as-is. These values are what the
+//! bundled C encoder, and Sega's, emit.
+
+/// Added to a run command's count to get the number of times its unit repeats;
+/// a count of 0 is a run of 2 units, the shortest run there is.
+pub const RUN_LENGTH_BIAS: usize = 2;
+
+/// The bit of a byte-mode count which marks a literal command.
+pub const LITERAL_FLAG_8: u8 = 0x80;
+/// The bit of a word-mode count which marks a literal command.
+pub const LITERAL_FLAG_16: u16 = 0x8000;
+/// The bit of a longword-mode count which marks a literal command.
+pub const LITERAL_FLAG_32: u32 = 0x8000_0000;
+
+/// The largest count a byte-mode run command can hold: a run of 129 units.
+pub const MAX_RUN_COUNT_8: i8 = i8::MAX;
+/// The largest count a word-mode run command can hold: a run of 32769 units.
+pub const MAX_RUN_COUNT_16: i16 = i16::MAX;
+/// The largest count a longword-mode run command can hold: a run of 2^31 + 1 units.
+pub const MAX_RUN_COUNT_32: i32 = i32::MAX;
+
+/// The most negative count a byte-mode literal command can hold: a literal of 128 units.
+pub const MIN_LITERAL_COUNT_8: i8 = i8::MIN;
+/// The most negative count a word-mode literal command can hold: a literal of 32768 units.
+pub const MIN_LITERAL_COUNT_16: i16 = i16::MIN;
+/// The most negative count a longword-mode literal command can hold: a literal of 2^31 units.
+pub const MIN_LITERAL_COUNT_32: i32 = i32::MIN;
@@ -0,0 +1,154 @@
+//! Falling back to storing data uncompressed when compression doesn't help.
+
+use std::io;
+use std::io::Write;
+
+use crate::decode::{block_len, sequence_block_len};
+use crate::{check_input, compress, create_header, CompressionError, Size};
+
+/// Data which has either been compressed, or stored as-is because compressing
+/// it wouldn't have made it any smaller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Packed {
+    /// The original, uncompressed data.
+    Stored(Vec<u8>),
+    /// A compressed payload, without a header.
+    Compressed(Vec<u8>),
+}
+
+impl Packed {
+    /// Returns `true` if the data was compressed.
+    pub fn is_compressed(&self) -> bool {
+        match *self {
+            Packed::Stored(_) => false,
+            Packed::Compressed(_) => true,
+        }
+    }
+
+    /// The stored or compressed bytes.
+    pub fn data(&self) -> &[u8] {
+        match *self {
+            Packed::Stored(ref data) | Packed::Compressed(ref data) => data,
+        }
+    }
+
+    /// Consumes the `Packed`, returning the stored or compressed bytes.
+    pub fn into_inner(self) -> Vec<u8> {
+        match self {
+            Packed::Stored(data) | Packed::Compressed(data) => data,
+        }
+    }
+}
+
+/// Returns the length, in bytes, below which data can never shrink when
+/// compressed in increments of `size`.
+///
+/// The cheapest command is a run, which takes two units - its count and the
+/// repeated unit - to encode at least two units, so only input of at least
+/// three units can come out smaller. The header is the same length whether the
+/// data is compressed or stored, so it doesn't change the threshold.
+/// `compress_or_passthrough` and `compress_block` store anything shorter
+/// without running the encoder at all.
+pub fn min_compressible_len(size: Size) -> usize {
+    3 * size.unit_len()
+}
+
+/// Compresses `data`, or returns it as `Packed::Stored` if compressing doesn't shrink it.
+fn pack(data: &[u8], size: Size) -> Result<Packed, CompressionError> {
+    if data.len() < min_compressible_len(size) {
+        check_input(data, size)?;
+        return Ok(Packed::Stored(data.to_vec()));
+    }
+    match compress(data, size) {
+        Ok(compressed) if compressed.len() < data.len() => Ok(Packed::Compressed(compressed)),
+        Ok(_) | Err(CompressionError::EncoderFailed) => Ok(Packed::Stored(data.to_vec())),
+        Err(e) => Err(e),
+    }
+}
+
+/// Compresses `data` in increments of `size`, falling back to storing it
+/// uncompressed if the compressed payload wouldn't be smaller than the input.
+///
+/// `name` identifies the data in the message logged through the `log` crate
+/// when it's stored uncompressed. Because the C encoder fails rather than
+/// producing output larger than its input, an encoder failure is treated as
+/// incompressible data; other errors, such as misaligned input, are returned.
+pub fn compress_or_passthrough(
+    name: &str,
+    data: &[u8],
+    size: Size,
+) -> Result<Packed, CompressionError> {
+    let packed = pack(data, size)?;
+    if !packed.is_compressed() {
+        log::info!(
+            "{} stored uncompressed: {} bytes don't shrink in {:?} mode",
+            name,
+            data.len(),
+            size
+        );
+    }
+    Ok(packed)
+}
+
+/// A complete, self-describing block, as returned by `compress_block`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compressed {
+    /// The CMP header describing the data.
+    pub header: Vec<u8>,
+    /// The compressed payload, or the original data if `stored` is set.
+    pub payload: Vec<u8>,
+    /// Whether `payload` holds the original data because compressing it wouldn't have shrunk it.
+    pub stored: bool,
+}
+
+impl Compressed {
+    /// Writes the header followed by the payload to `w`.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.header)?;
+        w.write_all(&self.payload)
+    }
+}
+
+/// Compresses `data` in increments of `size`, returning everything needed to
+/// write a complete block: the header, the payload, and whether the payload
+/// was stored uncompressed.
+///
+/// As with `compress_or_passthrough`, data which doesn't shrink is stored as-is;
+/// the header is the same either way, so the caller's own format must record `stored`.
+pub fn compress_block(data: &[u8], size: Size) -> Result<Compressed, CompressionError> {
+    let packed = pack(data, size)?;
+    Ok(Compressed {
+        header: create_header(data.len() as i32, size),
+        stored: !packed.is_compressed(),
+        payload: packed.into_inner(),
+    })
+}
+
+/// Joins two header-prefixed blocks into one stream, returning it along with
+/// the offset at which the second block begins.
+///
+/// Each block is checked to decode to exactly its header's declared size and
+/// to end exactly where the slice does, so that the result can be read back
+/// with `BlockIter`. A block with data after its end, such as padding, is
+/// rejected with `CompressionError::TrailingData`.
+///
+/// An empty `a` followed by a Word or Longword `b` has headers which also read
+/// as a single 32-bit header. `BlockIter` still splits them correctly unless
+/// the stream decodes both ways; the result is checked, and if `BlockIter`
+/// would find the boundary anywhere but the end of `a`, this returns
+/// `CompressionError::AmbiguousBlockBoundary`.
+pub fn concat_blocks(a: &[u8], b: &[u8]) -> Result<(Vec<u8>, usize), CompressionError> {
+    for block in [a, b].iter() {
+        let len = block_len(block)?;
+        if len != block.len() {
+            return Err(CompressionError::TrailingData(block.len() - len));
+        }
+    }
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    out.extend_from_slice(a);
+    out.extend_from_slice(b);
+    if sequence_block_len(&out)? != a.len() {
+        return Err(CompressionError::AmbiguousBlockBoundary);
+    }
+    Ok((out, a.len()))
+}
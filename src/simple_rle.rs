@@ -0,0 +1,49 @@
+//! The simplified run-length encoding used to illustrate RLE in the crate's
+//! documentation, where `AAAAAAAABBBBAABA` is described as `8A4B2A1B1A`.
+//!
+//! **This is not CMP**, and nothing else in the crate reads or writes it. It's
+//! a readable reference for how run-length encoding works before getting into
+//! CMP's actual command format, which `compress` implements. Every run is
+//! written as a single ASCII digit from `1` to `9` giving its length, followed
+//! by the byte which repeats; runs longer than 9 bytes are split.
+//!
+//! ```
+//! use sega_cmp::simple_rle;
+//!
+//! assert_eq!(simple_rle::encode(b"AAAAAAAABBBBAABA"), b"8A4B2A1B1A".to_vec());
+//! assert_eq!(simple_rle::decode(b"8A4B2A1B1A").unwrap(), b"AAAAAAAABBBBAABA".to_vec());
+//! ```
+
+/// The longest run which fits in a single digit.
+const MAX_RUN: usize = 9;
+
+/// Encodes `data` as a sequence of digit and byte pairs.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for run in data.chunk_by(|a, b| a == b) {
+        for piece in run.chunks(MAX_RUN) {
+            out.push(b'0' + piece.len() as u8);
+            out.push(piece[0]);
+        }
+    }
+    out
+}
+
+/// Decodes data written by `encode`.
+///
+/// Returns `None` if `encoded` doesn't consist of digit and byte pairs,
+/// with each digit from `1` to `9`.
+pub fn decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    if !encoded.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut out = Vec::new();
+    for pair in encoded.chunks(2) {
+        let count = match pair[0] {
+            b'1'..=b'9' => (pair[0] - b'0') as usize,
+            _ => return None,
+        };
+        out.extend(std::iter::repeat_n(pair[1], count));
+    }
+    Some(out)
+}
@@ -0,0 +1,115 @@
+//! Splitting compressed output into blocks no larger than a fixed size.
+
+use crate::decode::{Command, Commands};
+use crate::encode::push_count;
+use crate::{compress, create_header, header_len_for, CompressionError, Size};
+
+/// Collects commands into blocks of at most `max_len` bytes, header included.
+struct Blocks {
+    size: Size,
+    max_len: usize,
+    blocks: Vec<Vec<u8>>,
+    payload: Vec<u8>,
+    // The number of bytes the current block's payload decompresses to
+    decompressed: usize,
+}
+
+impl Blocks {
+    /// The number of bytes left in the current block for a command which
+    /// would make it decompress to `decompressed` more bytes.
+    fn space(&self, decompressed: usize) -> usize {
+        let header_len = header_len_for((self.decompressed + decompressed) as u32);
+        self.max_len.saturating_sub(header_len + self.payload.len())
+    }
+
+    fn flush(&mut self) {
+        let mut block = create_header(self.decompressed as i32, self.size);
+        block.append(&mut self.payload);
+        self.blocks.push(block);
+        self.decompressed = 0;
+    }
+
+    /// Starts a new block if the current one has anything in it, or fails if
+    /// it's already empty, since then nothing will ever fit.
+    fn next_block(&mut self, needed: usize) -> Result<(), CompressionError> {
+        if self.payload.is_empty() {
+            return Err(CompressionError::BudgetExceeded {
+                actual: needed,
+                budget: self.max_len,
+            });
+        }
+        self.flush();
+        Ok(())
+    }
+}
+
+/// Compresses `data` in increments of `size`, split into independently
+/// decodable blocks of at most `max_len` bytes each, header included.
+///
+/// `data` is compressed as with `compress`, and its commands are then divided
+/// between blocks in order, starting a new block whenever the next command
+/// wouldn't fit in the current one. Each block begins with its own header, and
+/// runs are never split across blocks; a literal which doesn't fit is split so
+/// that its first units fill the current block. Concatenating every block's
+/// decompressed data gives back `data`. Empty input gives a single block
+/// holding only a header.
+///
+/// Returns `CompressionError::BudgetExceeded` if `max_len` is too small to hold
+/// even a header and one command.
+pub fn compress_max_block(
+    data: &[u8],
+    size: Size,
+    max_len: usize,
+) -> Result<Vec<Vec<u8>>, CompressionError> {
+    let payload = compress(data, size)?;
+    let width = size.unit_len();
+    let mut blocks = Blocks {
+        size,
+        max_len,
+        blocks: Vec::new(),
+        payload: Vec::new(),
+        decompressed: 0,
+    };
+
+    // The payload came from our own encoder, so it's always well-formed
+    for command in Commands::new(&payload, size).flatten() {
+        match command {
+            Command::Run { unit, count } => {
+                let len = count * width;
+                if blocks.space(len) < 2 * width {
+                    blocks.next_block(header_len_for(len as u32) + 2 * width)?;
+                }
+                push_count(&mut blocks.payload, width, count as i64 - 2);
+                blocks.payload.extend_from_slice(unit);
+                blocks.decompressed += len;
+            }
+            Command::Literal { mut units } => {
+                while !units.is_empty() {
+                    let fits = (blocks.space(units.len()) / width).saturating_sub(1);
+                    if fits == 0 {
+                        blocks.next_block(header_len_for(width as u32) + 2 * width)?;
+                        continue;
+                    }
+                    let (head, rest) = units.split_at(fits.min(units.len() / width) * width);
+                    push_count(&mut blocks.payload, width, -((head.len() / width) as i64));
+                    blocks.payload.extend_from_slice(head);
+                    blocks.decompressed += head.len();
+                    units = rest;
+                }
+            }
+        }
+    }
+    if blocks.blocks.is_empty() && blocks.payload.is_empty() {
+        let header_len = header_len_for(0);
+        if max_len < header_len {
+            return Err(CompressionError::BudgetExceeded {
+                actual: header_len,
+                budget: max_len,
+            });
+        }
+        blocks.flush();
+    } else if !blocks.payload.is_empty() {
+        blocks.flush();
+    }
+    Ok(blocks.blocks)
+}
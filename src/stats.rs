@@ -0,0 +1,278 @@
+//! Measuring how well data compresses.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::decode::{Command, Commands};
+use crate::{compress, compress_units, CompressionError, Size};
+
+/// Sizes recorded while compressing a buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompressionStats {
+    /// The length of the uncompressed input, in bytes.
+    pub original_len: usize,
+    /// The length of the compressed payload, in bytes, not including a header.
+    pub compressed_len: usize,
+    /// Non-fatal problems noticed while compressing, such as data which
+    /// compressed but barely got any smaller.
+    pub warnings: Vec<String>,
+}
+
+impl CompressionStats {
+    /// The compressed length as a fraction of the original length;
+    /// lower is better. Empty inputs have a ratio of 1.
+    pub fn ratio(&self) -> f64 {
+        if self.original_len == 0 {
+            return 1.0;
+        }
+        self.compressed_len as f64 / self.original_len as f64
+    }
+}
+
+/// Compressed data whose ratio is above this saved too little to be worth
+/// decompressing, and gets a warning.
+const POOR_RATIO: f64 = 0.9;
+
+/// Compresses `data` in increments of `size`, as with `compress`, returning
+/// statistics about the compression along with the compressed data.
+///
+/// The C encoder only reports anything when it fails, so the warnings in the
+/// returned statistics come from checks on its output: currently, a warning is
+/// given when the payload is more than 90% of the size of the input.
+pub fn compress_with_stats(
+    data: &[u8],
+    size: Size,
+) -> Result<(Vec<u8>, CompressionStats), CompressionError> {
+    let compressed = compress(data, size)?;
+    let mut stats = CompressionStats {
+        original_len: data.len(),
+        compressed_len: compressed.len(),
+        warnings: Vec::new(),
+    };
+    if stats.ratio() > POOR_RATIO {
+        stats.warnings.push(format!(
+            "Data is poorly compressible: {} bytes compressed to {} ({:.0}%)",
+            stats.original_len,
+            stats.compressed_len,
+            stats.ratio() * 100.0
+        ));
+    }
+    Ok((compressed, stats))
+}
+
+/// Compresses `data` in increments of `size`, returning `None` rather than the
+/// compressed payload if it saves less than `min_savings` of the input.
+///
+/// `min_savings` is a fraction of the input's length, so `0.1` requires the
+/// payload to be at least 10% smaller than `data`; it's `1 - ratio()` in terms
+/// of `CompressionStats`. Data the C encoder can't compress without expanding
+/// is also `None`, while other errors, such as misaligned input, are returned.
+/// Empty inputs save nothing.
+pub fn compress_if_worthwhile(
+    data: &[u8],
+    size: Size,
+    min_savings: f64,
+) -> Result<Option<Vec<u8>>, CompressionError> {
+    let compressed = match compress(data, size) {
+        Ok(compressed) => compressed,
+        Err(CompressionError::EncoderFailed) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let stats = CompressionStats {
+        original_len: data.len(),
+        compressed_len: compressed.len(),
+        warnings: Vec::new(),
+    };
+    if 1.0 - stats.ratio() < min_savings {
+        return Ok(None);
+    }
+    Ok(Some(compressed))
+}
+
+/// How well a single file compressed, as returned by `report`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileReport {
+    /// The name the file was passed to `report` with.
+    pub name: String,
+    /// The length of the uncompressed file, in bytes.
+    pub original_len: usize,
+    /// The length of the compressed payload, or `None` if the file couldn't be compressed.
+    pub compressed_len: Option<usize>,
+    /// The compressed length as a fraction of the original length,
+    /// or `None` if the file couldn't be compressed.
+    pub ratio: Option<f64>,
+}
+
+impl FileReport {
+    /// The number of bytes saved by compressing this file; zero if it couldn't be compressed.
+    pub fn savings(&self) -> usize {
+        match self.compressed_len {
+            Some(len) => self.original_len.saturating_sub(len),
+            None => 0,
+        }
+    }
+}
+
+/// Compresses each of `files` in increments of `size` and reports how well each one compressed.
+///
+/// Each file is given as its name and contents. The reports are sorted by the
+/// number of bytes saved, largest first; files which couldn't be compressed
+/// (because they would have grown, or aren't a multiple of `size`) come last.
+pub fn report(files: &[(String, Vec<u8>)], size: Size) -> Vec<FileReport> {
+    let mut reports: Vec<FileReport> = files
+        .iter()
+        .map(|(name, data)| {
+            let stats = compress_with_stats(data, size).ok().map(|(_, stats)| stats);
+            FileReport {
+                name: name.clone(),
+                original_len: data.len(),
+                compressed_len: stats.as_ref().map(|stats| stats.compressed_len),
+                ratio: stats.as_ref().map(CompressionStats::ratio),
+            }
+        })
+        .collect();
+
+    reports.sort_by(|a, b| {
+        b.compressed_len
+            .is_some()
+            .cmp(&a.compressed_len.is_some())
+            .then(b.savings().cmp(&a.savings()))
+    });
+    reports
+}
+
+/// Colour-level statistics about a palette, as returned by `compress_palette`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PaletteStats {
+    /// The number of different colours in the palette.
+    pub distinct_colors: usize,
+    /// The most times a single colour repeats back to back.
+    pub longest_run: usize,
+}
+
+/// Compresses a palette of 16-bit colours, such as the Saturn's RGB555
+/// entries, in word mode, returning statistics about its colours along with
+/// the compressed data.
+///
+/// Colours are written big-endian, as the Saturn stores them, so the output is
+/// identical to compressing the palette's in-memory bytes on the Saturn with
+/// `Size::Word`.
+pub fn compress_palette(colors: &[u16]) -> Result<(Vec<u8>, PaletteStats), CompressionError> {
+    let compressed = compress_units(colors)?;
+
+    let mut distinct = colors.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let longest_run = colors
+        .chunk_by(|a, b| a == b)
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+
+    let stats = PaletteStats {
+        distinct_colors: distinct.len(),
+        longest_run,
+    };
+    Ok((compressed, stats))
+}
+
+/// A rough cost of decompressing a payload on the Saturn, as returned by
+/// `estimate_decode_cost`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecodeCost {
+    /// The number of commands in the payload.
+    pub commands: usize,
+    /// The number of units the payload decompresses to.
+    pub units: usize,
+    /// An approximate number of SH-2 cycles the decoder spends on the payload.
+    pub cycles: u64,
+}
+
+// Approximate SH-2 cycles for a typical decoder loop, assuming no wait states:
+// reading a count and branching on its sign, storing one unit of a run, and
+// loading and storing one unit of a literal. These are estimates from the
+// instruction counts of such a loop, not measurements of any game's decoder.
+const COMMAND_CYCLES: u64 = 8;
+const RUN_UNIT_CYCLES: u64 = 3;
+const LITERAL_UNIT_CYCLES: u64 = 5;
+
+/// Estimates how long the Saturn takes to decompress a headerless payload
+/// which was compressed in increments of `size`.
+///
+/// The cost model charges a fixed number of cycles per command and per unit
+/// written, with literal units costing more than run units since each has to
+/// be loaded as well as stored. Every `Size` is charged the same per unit, as
+/// the SH-2 moves a byte, word or longword in a single access. Memory wait
+/// states, cache misses and DMA aren't modelled, so real timings will be
+/// higher; the estimate is best used to compare assets with one another
+/// rather than as an absolute figure. The payload is checked to be well-formed
+/// as it's walked, as with `decompressed_len`.
+pub fn estimate_decode_cost(blob: &[u8], size: Size) -> Result<DecodeCost, CompressionError> {
+    let width = size.unit_len();
+    let mut cost = DecodeCost {
+        commands: 0,
+        units: 0,
+        cycles: 0,
+    };
+    for command in Commands::new(blob, size) {
+        let command = command?;
+        let units = command.decompressed_len() / width;
+        let unit_cycles = match command {
+            Command::Run { .. } => RUN_UNIT_CYCLES,
+            Command::Literal { .. } => LITERAL_UNIT_CYCLES,
+        };
+        cost.commands += 1;
+        cost.units += units;
+        cost.cycles += COMMAND_CYCLES + unit_cycles * units as u64;
+    }
+    Ok(cost)
+}
+
+/// How many commands of each length a payload contains, as returned by
+/// `command_histogram`.
+///
+/// Lengths are counted in units, and grouped into power-of-two buckets: index
+/// `i` counts the commands of `2^i` to `2^(i + 1) - 1` units, so index 0 holds
+/// single-unit literals, index 1 holds commands of 2 or 3 units, and so on.
+/// Each list only goes up to the last non-empty bucket.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandHistogram {
+    /// The number of run commands in each length bucket.
+    pub runs: Vec<usize>,
+    /// The number of literal commands in each length bucket.
+    pub literals: Vec<usize>,
+}
+
+fn count_bucket(buckets: &mut Vec<usize>, units: usize) {
+    let bucket = units.ilog2() as usize;
+    if buckets.len() <= bucket {
+        buckets.resize(bucket + 1, 0);
+    }
+    buckets[bucket] += 1;
+}
+
+/// Counts the runs and literals of each length in a headerless payload which
+/// was compressed in increments of `size`.
+///
+/// This walks the whole payload, so it's kept separate from
+/// `compress_with_stats`; it's meant for profiling whether an asset is
+/// dominated by short runs, long runs or literals. The payload is checked to
+/// be well-formed as it's walked, as with `decompressed_len`.
+pub fn command_histogram(blob: &[u8], size: Size) -> Result<CommandHistogram, CompressionError> {
+    let width = size.unit_len();
+    let mut histogram = CommandHistogram::default();
+    for command in Commands::new(blob, size) {
+        let command = command?;
+        let units = command.decompressed_len() / width;
+        match command {
+            Command::Run { .. } => count_bucket(&mut histogram.runs, units),
+            Command::Literal { .. } => count_bucket(&mut histogram.literals, units),
+        }
+    }
+    Ok(histogram)
+}
@@ -0,0 +1,419 @@
+//! `Read` and `Write` adapters modelled after `flate2`'s `ZlibEncoder` and `ZlibDecoder`.
+//!
+//! CMP isn't a streaming format - the header needs the full decompressed size,
+//! and the encoder needs the full input - so both adapters buffer their entire
+//! input in memory and do the actual work through `compress` and `decompress`.
+//! `CmpCursor` adds `Seek` on top of reading, for random access.
+
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::decode::{block_len, decompress_with_header, Command, Commands};
+use crate::header::{create_header, parse_header, Header, HeaderWidth};
+use crate::{compress, CompressionError, Size};
+
+fn to_io_error(error: CompressionError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Compresses data written to it, writing a CMP header and payload to the
+/// underlying writer once `finish` is called.
+///
+/// Like `flate2`'s encoders, dropping a `CmpEncoder` without calling `finish`
+/// will attempt to finish the stream and ignore any errors.
+pub struct CmpEncoder<W: Write> {
+    inner: Option<W>,
+    size: Size,
+    buf: Vec<u8>,
+    /// The header and payload for `buf`, once compressed, until all of it has
+    /// been written
+    pending: Vec<u8>,
+    /// How much of `pending` has been written so far
+    written: usize,
+    /// Whether everything written so far has been compressed and written out
+    finished: bool,
+}
+
+impl<W: Write> CmpEncoder<W> {
+    /// Creates a new encoder which will compress in increments of `size` and write to `w`.
+    pub fn new(w: W, size: Size) -> CmpEncoder<W> {
+        CmpEncoder {
+            inner: Some(w),
+            size,
+            buf: Vec::new(),
+            pending: Vec::new(),
+            written: 0,
+            finished: false,
+        }
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Writing directly to the underlying writer will corrupt the output stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Compresses all data written so far and writes it to the underlying writer,
+    /// without consuming the encoder.
+    ///
+    /// As with `flate2`, this can be called repeatedly: once the stream has been
+    /// finished, further calls write nothing. If compressing or writing fails,
+    /// nothing is lost, and calling it again carries on from where it stopped.
+    /// Data written after this call will be compressed as a separate stream.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        if self.pending.is_empty() {
+            let compressed = compress(&self.buf, self.size).map_err(to_io_error)?;
+            self.pending = create_header(self.buf.len() as i32, self.size);
+            self.pending.extend_from_slice(&compressed);
+        }
+
+        let w = self.inner.as_mut().unwrap();
+        while self.written < self.pending.len() {
+            match w.write(&self.pending[self.written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        w.flush()?;
+
+        self.buf.clear();
+        self.pending.clear();
+        self.written = 0;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Compresses all data written so far, writes it to the underlying writer,
+    /// and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for CmpEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A stream which was only partly written out has to be completed
+        // before anything is added to the next one
+        if !self.pending.is_empty() {
+            self.try_finish()?;
+        }
+        if !buf.is_empty() {
+            self.finished = false;
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Does nothing, since no output can be produced until the stream is finished.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for CmpEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && (!self.buf.is_empty() || !self.pending.is_empty()) {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+/// Decompresses a CMP stream, header included, read from the underlying reader.
+///
+/// The whole stream is read and decompressed on the first call to `read`.
+/// Only the bytes which make up the stream are read, command by command, so
+/// anything following it in the underlying reader is left there to be read
+/// through `into_inner`. The exception is a stream which decompresses to
+/// nothing: its header can't be told apart from the start of a 32-bit one
+/// without looking at the next 4 bytes. If those bytes could also start a
+/// header, the stream is read as a 32-bit one only if the block it declares can
+/// be read in full, as `BlockIter` does; otherwise it's read as empty, and
+/// everything read while trying the longer reading is consumed.
+pub struct CmpDecoder<R: Read> {
+    inner: R,
+    decoded: Option<io::Cursor<Vec<u8>>>,
+}
+
+impl<R: Read> CmpDecoder<R> {
+    /// Creates a new decoder which will read a CMP stream from `r`.
+    pub fn new(r: R) -> CmpDecoder<R> {
+        CmpDecoder {
+            inner: r,
+            decoded: None,
+        }
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader before the stream has been
+    /// decoded will corrupt the output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consumes the decoder, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CmpDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.decoded.is_none() {
+            let data = read_block(&mut self.inner)?;
+            let decoded = decompress_with_header(&data).map_err(to_io_error)?;
+            self.decoded = Some(io::Cursor::new(decoded));
+        }
+        self.decoded.as_mut().unwrap().read(buf)
+    }
+}
+
+/// Appends bytes from `r` to `data` until it's `len` bytes long, returning
+/// whether the reader held that many.
+///
+/// The bytes are read as they arrive rather than allocated up front, so a
+/// corrupt count can't make this allocate more than the reader holds.
+fn fill<R: Read>(r: &mut R, data: &mut Vec<u8>, len: usize) -> io::Result<bool> {
+    if data.len() < len {
+        let wanted = (len - data.len()) as u64;
+        return Ok(r.take(wanted).read_to_end(data)? as u64 == wanted);
+    }
+    Ok(true)
+}
+
+/// Reads exactly one header-prefixed CMP stream from `r`, stopping once its
+/// commands produce the size declared in its header.
+///
+/// An empty 16-bit header followed by one which could start a block is read
+/// the way `BlockIter` reads it: as a 32-bit header only if the block it
+/// declares can be read in full, and otherwise as an empty stream.
+fn read_block<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(4).read_to_end(&mut data)?;
+    // An empty 16-bit size field may be followed by a 32-bit one
+    if data.len() == 4 && data[2..4] == [0, 0] {
+        r.take(4).read_to_end(&mut data)?;
+    }
+    let header = parse_header(&data).map_err(to_io_error)?;
+    let ambiguous = header.width == HeaderWidth::Bits32 && parse_header(&data[4..]).is_ok();
+    if !read_commands(r, &mut data, header)? {
+        if ambiguous {
+            data.truncate(4);
+            return Ok(data);
+        }
+        return Err(to_io_error(CompressionError::Truncated));
+    }
+    Ok(data)
+}
+
+/// Reads the commands of the block whose header has been read into `data`
+/// from `r`, appending them to `data`, and returns whether the reader held
+/// enough of them to produce the header's declared size.
+fn read_commands<R: Read>(r: &mut R, data: &mut Vec<u8>, header: Header) -> io::Result<bool> {
+    let width = header.size.unit_len();
+    let expected = header.decompressed_size as usize;
+
+    let mut pos = header.encoded_len();
+    let mut produced = 0usize;
+    while produced < expected {
+        if !fill(r, data, pos + width)? {
+            return Ok(false);
+        }
+        let count = match data[pos..pos + width] {
+            [a] => a as i8 as i64,
+            [a, b] => i16::from_be_bytes([a, b]) as i64,
+            [a, b, c, d] => i32::from_be_bytes([a, b, c, d]) as i64,
+            _ => unreachable!(),
+        };
+        pos += width;
+        let (operand, output) = if count >= 0 {
+            (width, (count as usize + 2) * width)
+        } else {
+            let len = (-count) as usize * width;
+            (len, len)
+        };
+        pos += operand;
+        if !fill(r, data, pos)? {
+            return Ok(false);
+        }
+        produced += output;
+    }
+    Ok(true)
+}
+
+/// Reads and seeks within the decompressed contents of a header-prefixed CMP
+/// stream, for formats which need random access such as jumping to a record.
+///
+/// There are two ways for it to get at the data, with different costs:
+///
+/// * `new` decompresses the whole stream up front, holding the output in
+///   memory for the cursor's lifetime. Reads and seeks are then as cheap as
+///   they are on an `io::Cursor`, at the cost of memory equal to the
+///   decompressed size.
+/// * `redecoding` borrows the compressed stream and decodes it lazily, so it
+///   only needs memory for its own position. Reading forward continues from
+///   the current command, so reading the whole stream in order costs no more
+///   than decompressing it; seeking backward restarts decoding from the
+///   start of the payload. Commands before the target are skipped without
+///   being expanded, so that costs time proportional to the number of
+///   commands before the target rather than its offset.
+///
+/// Both check that the whole payload is well-formed and decodes to the size in
+/// its header when they're created, so reads never fail.
+pub struct CmpCursor<'a> {
+    inner: Inner<'a>,
+}
+
+enum Inner<'a> {
+    Buffered(io::Cursor<Vec<u8>>),
+    Redecoding(Redecoder<'a>),
+}
+
+impl CmpCursor<'static> {
+    /// Decompresses the stream in `data`, returning a cursor over the output.
+    pub fn new(data: &[u8]) -> Result<CmpCursor<'static>, CompressionError> {
+        let decoded = decompress_with_header(data)?;
+        Ok(CmpCursor {
+            inner: Inner::Buffered(io::Cursor::new(decoded)),
+        })
+    }
+}
+
+impl<'a> CmpCursor<'a> {
+    /// Returns a cursor which decodes the stream in `data` as it's read,
+    /// rather than holding its decompressed contents in memory.
+    pub fn redecoding(data: &'a [u8]) -> Result<CmpCursor<'a>, CompressionError> {
+        let end = block_len(data)?;
+        let header = parse_header(data)?;
+        let payload = &data[header.encoded_len()..end];
+        Ok(CmpCursor {
+            inner: Inner::Redecoding(Redecoder {
+                payload,
+                size: header.size,
+                len: header.decompressed_size as usize,
+                pos: 0,
+                commands: Commands::new(payload, header.size),
+                current: None,
+                current_start: 0,
+            }),
+        })
+    }
+
+    /// The size of the decompressed data, in bytes.
+    pub fn decompressed_len(&self) -> usize {
+        match self.inner {
+            Inner::Buffered(ref cursor) => cursor.get_ref().len(),
+            Inner::Redecoding(ref decoder) => decoder.len,
+        }
+    }
+}
+
+impl<'a> Read for CmpCursor<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner {
+            Inner::Buffered(ref mut cursor) => cursor.read(buf),
+            Inner::Redecoding(ref mut decoder) => decoder.read(buf),
+        }
+    }
+}
+
+impl<'a> Seek for CmpCursor<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self.inner {
+            Inner::Buffered(ref mut cursor) => cursor.seek(pos),
+            Inner::Redecoding(ref mut decoder) => {
+                let new_pos = match pos {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(offset) => (decoder.len as u64).checked_add_signed(offset),
+                    SeekFrom::Current(offset) => decoder.pos.checked_add_signed(offset),
+                };
+                decoder.pos = new_pos.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?;
+                Ok(decoder.pos)
+            }
+        }
+    }
+}
+
+/// The state of a `CmpCursor` which decodes as it goes.
+struct Redecoder<'a> {
+    payload: &'a [u8],
+    size: Size,
+    len: usize,
+    /// The position reads start from, which may be past the end
+    pos: u64,
+    commands: Commands<'a>,
+    /// The command containing the last byte read, if any
+    current: Option<Command<'a>>,
+    /// The offset in the decompressed data at which `current` starts
+    current_start: usize,
+}
+
+impl<'a> Redecoder<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len as u64 {
+            return Ok(0);
+        }
+        let pos = self.pos as usize;
+        if pos < self.current_start {
+            self.commands = Commands::new(self.payload, self.size);
+            self.current = None;
+            self.current_start = 0;
+        }
+
+        let command = loop {
+            if let Some(command) = self.current {
+                let end = self.current_start + command.decompressed_len();
+                if pos < end {
+                    break command;
+                }
+                self.current_start = end;
+            }
+            // The payload was checked when the cursor was created, so it
+            // holds enough commands to reach the end of the data
+            self.current = match self.commands.next() {
+                Some(command) => Some(command.map_err(to_io_error)?),
+                None => return Err(to_io_error(CompressionError::Truncated)),
+            };
+        };
+
+        let offset = pos - self.current_start;
+        // The last command may overshoot the size in the header
+        let available = command
+            .decompressed_len()
+            .min(self.len - self.current_start)
+            - offset;
+        let n = available.min(buf.len());
+        match command {
+            Command::Literal { units } => buf[..n].copy_from_slice(&units[offset..offset + n]),
+            Command::Run { unit, .. } => {
+                for (i, byte) in buf[..n].iter_mut().enumerate() {
+                    *byte = unit[(offset + i) % unit.len()];
+                }
+            }
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
@@ -0,0 +1,6 @@
+//! Scaffolding for testing CMP encoders, this crate's or your own.
+//!
+//! Enabled by the `testutil` feature. Nothing here is needed to compress or
+//! decompress data.
+
+pub mod adversarial;
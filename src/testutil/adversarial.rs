@@ -0,0 +1,108 @@
+//! Pathological inputs which stress the boundaries where run-length encoders
+//! tend to have off-by-one bugs.
+//!
+//! Every generator returns whole units of the given `Size`, with word and
+//! longword units written big-endian. The longest longword run and literal
+//! are over 2^31 units, far too large to build in memory, so longword inputs
+//! use the same lengths as word inputs; their boundaries are only covered by
+//! byte and word inputs. Many of these inputs are incompressible on purpose,
+//! so compressing them may fail with `CompressionError::EncoderFailed`; an
+//! encoder under test should fail on exactly the same ones.
+
+use crate::encode::max_literal;
+use crate::Size;
+
+/// The longest literal, in units, whose boundaries can be tested in `size`.
+fn literal_limit(size: Size) -> usize {
+    match size {
+        Size::Longword => max_literal(Size::Word),
+        size => max_literal(size),
+    }
+}
+
+/// Writes unit `value`, truncated to `size`'s width, to `out` `count` times.
+fn push_units(out: &mut Vec<u8>, size: Size, value: u32, count: usize) {
+    let bytes = value.to_be_bytes();
+    let unit = &bytes[4 - size.unit_len()..];
+    for _ in 0..count {
+        out.extend_from_slice(unit);
+    }
+}
+
+/// The value of unit `i` of a sequence in which no two neighbouring units are
+/// equal, and no unit is zero.
+fn distinct(i: usize) -> u32 {
+    (i % 255) as u32 + 1
+}
+
+/// `units` units alternating between two values, so there are no runs at all:
+/// the worst case for run-length encoding.
+pub fn alternating(size: Size, units: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for i in 0..units {
+        push_units(&mut out, size, (i % 2) as u32 + 1, 1);
+    }
+    out
+}
+
+/// `units` units, none equal to the next, cycling through 255 values.
+pub fn all_distinct(size: Size, units: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for i in 0..units {
+        push_units(&mut out, size, distinct(i), 1);
+    }
+    out
+}
+
+/// Runs of every length from 1 to 4 units, and from two below to two above
+/// the longest run a single command can hold, each of a different unit from
+/// the runs either side of it.
+pub fn boundary_runs(size: Size) -> Vec<u8> {
+    let longest = literal_limit(size) + 1;
+    let lengths = (1..=4).chain(longest - 2..=longest + 2);
+    let mut out = Vec::new();
+    for (i, len) in lengths.enumerate() {
+        push_units(&mut out, size, distinct(i), len);
+    }
+    out
+}
+
+/// Literals from one below to one above the longest literal a single command
+/// can hold, each followed by a run of 3 zero units so they can't merge.
+pub fn boundary_literals(size: Size) -> Vec<u8> {
+    let longest = literal_limit(size);
+    let mut out = Vec::new();
+    for len in longest - 1..=longest + 1 {
+        out.extend(all_distinct(size, len));
+        push_units(&mut out, size, 0, 3);
+    }
+    out
+}
+
+/// Runs of 2 and 3 units interrupting a literal, where the encoder decides
+/// whether a run is worth splitting the literal for, including at the very
+/// start and end of the input.
+pub fn interrupted_literals(size: Size) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_units(&mut out, size, 0, 2);
+    out.extend(all_distinct(size, 5));
+    push_units(&mut out, size, 0, 2);
+    out.extend(all_distinct(size, 5));
+    push_units(&mut out, size, 0, 3);
+    out.extend(all_distinct(size, literal_limit(size) - 1));
+    push_units(&mut out, size, 0, 2);
+    out.extend(all_distinct(size, 1));
+    push_units(&mut out, size, 0, 2);
+    out
+}
+
+/// Every generator's input for `size`, each with the generator's name.
+pub fn all(size: Size) -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("alternating", alternating(size, 300)),
+        ("all_distinct", all_distinct(size, 300)),
+        ("boundary_runs", boundary_runs(size)),
+        ("boundary_literals", boundary_literals(size)),
+        ("interrupted_literals", interrupted_literals(size)),
+    ]
+}
@@ -0,0 +1,88 @@
+//! Compressing text with its control codes moved out of the way.
+//!
+//! Enabled by the `text` feature. Dialogue scripts are often full of control
+//! codes - line breaks, waits, colour changes - which break up runs that would
+//! otherwise compress well. `compress_text` takes the control codes out of the
+//! text, records where they were, and compresses what's left.
+//!
+//! **This isn't a stock CMP format.** A text stream is laid out as follows,
+//! with all integers big-endian:
+//!
+//! * the number of control codes, as a 32-bit integer
+//! * for each control code, in order, its offset in the original text as a
+//!   32-bit integer, followed by the code itself
+//! * a header-prefixed CMP stream, compressed in byte mode, holding the text
+//!   with its control codes removed
+//!
+//! Only `decompress_text`, or a loader following the same layout, can read it.
+
+use crate::{compress_with_header, decompress_with_header, CompressionError, Size};
+
+/// Compresses `text` in byte mode after removing every byte for which
+/// `is_control` returns `true`, returning a stream in the format described in
+/// the module documentation.
+///
+/// Texts longer than 4 GiB can't be described by the control code map, and are
+/// rejected with `CompressionError::InputTooLarge`.
+pub fn compress_text<F: Fn(u8) -> bool>(
+    text: &[u8],
+    is_control: F,
+) -> Result<Vec<u8>, CompressionError> {
+    if text.len() > u32::MAX as usize {
+        return Err(CompressionError::InputTooLarge);
+    }
+    let mut codes = Vec::new();
+    let mut plain = Vec::with_capacity(text.len());
+    for (offset, &byte) in text.iter().enumerate() {
+        if is_control(byte) {
+            codes.push((offset as u32, byte));
+        } else {
+            plain.push(byte);
+        }
+    }
+
+    let mut out = Vec::with_capacity(4 + codes.len() * 5);
+    out.extend_from_slice(&(codes.len() as u32).to_be_bytes());
+    for (offset, code) in codes {
+        out.extend_from_slice(&offset.to_be_bytes());
+        out.push(code);
+    }
+    out.extend_from_slice(&compress_with_header(&plain, Size::Byte)?);
+    Ok(out)
+}
+
+/// Reconstructs the text compressed by `compress_text`.
+///
+/// Returns `CompressionError::Truncated` if the control code map is cut short,
+/// and `CompressionError::InvalidHeader` if its offsets aren't in increasing
+/// order or point past the end of the text.
+pub fn decompress_text(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let read_u32 = |pos: usize| -> Result<usize, CompressionError> {
+        let bytes = data.get(pos..pos + 4).ok_or(CompressionError::Truncated)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+    let count = read_u32(0)?;
+    let map_len = count
+        .checked_mul(5)
+        .and_then(|len| len.checked_add(4))
+        .filter(|&len| len <= data.len())
+        .ok_or(CompressionError::Truncated)?;
+
+    let plain = decompress_with_header(&data[map_len..])?;
+    let mut out = Vec::with_capacity(plain.len() + count);
+    let mut plain = plain.iter();
+    for i in 0..count {
+        let pos = 4 + i * 5;
+        let offset = read_u32(pos)?;
+        if offset < out.len() {
+            return Err(CompressionError::InvalidHeader);
+        }
+        // Copy the text up to this control code, then the code itself
+        for _ in out.len()..offset {
+            out.push(*plain.next().ok_or(CompressionError::InvalidHeader)?);
+        }
+        out.push(data[pos + 4]);
+    }
+    out.extend(plain);
+    Ok(out)
+}
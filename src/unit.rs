@@ -0,0 +1,62 @@
+//! Compressing typed slices of bytes, words or longwords.
+
+use std::borrow::Cow;
+
+use crate::{compress, CompressionError, Size};
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// A type holding a single unit of one of the three `Size`s: `u8` for bytes,
+/// `u16` for words and `u32` for longwords.
+///
+/// This trait is sealed; it's only implemented for those three types.
+pub trait Unit: Copy + private::Sealed {
+    /// The size this type is compressed in.
+    const SIZE: Size;
+
+    /// Lays `units` out as the Saturn would store them in memory: big-endian.
+    fn to_be_bytes(units: &[Self]) -> Cow<'_, [u8]>;
+}
+
+impl Unit for u8 {
+    const SIZE: Size = Size::Byte;
+
+    fn to_be_bytes(units: &[u8]) -> Cow<'_, [u8]> {
+        Cow::Borrowed(units)
+    }
+}
+
+impl Unit for u16 {
+    const SIZE: Size = Size::Word;
+
+    fn to_be_bytes(units: &[u16]) -> Cow<'_, [u8]> {
+        Cow::Owned(units.iter().flat_map(|unit| unit.to_be_bytes()).collect())
+    }
+}
+
+impl Unit for u32 {
+    const SIZE: Size = Size::Longword;
+
+    fn to_be_bytes(units: &[u32]) -> Cow<'_, [u8]> {
+        Cow::Owned(units.iter().flat_map(|unit| unit.to_be_bytes()).collect())
+    }
+}
+
+/// Compresses a slice of units in the `Size` their type corresponds to.
+///
+/// A slice of units is always a whole number of units long, so unlike
+/// `compress` this can never fail with `CompressionError::Misaligned`. Units
+/// are written big-endian, as the Saturn stores them, so compressing a `&[u16]`
+/// is identical to compressing its in-memory bytes on the Saturn with
+/// `Size::Word`. Word and longword slices are always copied into a big-endian
+/// byte buffer first, whatever the host's byte order; byte slices are
+/// compressed in place.
+pub fn compress_units<U: Unit>(data: &[U]) -> Result<Vec<u8>, CompressionError> {
+    compress(&U::to_be_bytes(data), U::SIZE)
+}
@@ -0,0 +1,390 @@
+//! Checks on existing compressed data.
+
+use std::convert::TryFrom;
+
+use crate::decode::{Command, Commands};
+use crate::encode::max_run;
+use crate::header::parse_header;
+use crate::{
+    compress, compress_with_header, compress_with_options, create_header, decompress,
+    decompress_with_header, decompressed_len, CompressOptions, CompressionError, Effort, Size,
+};
+
+/// Returns whether recompressing a headerless payload produces exactly the same bytes.
+///
+/// The payload is decompressed in increments of `size`, then compressed again
+/// with this crate's encoder. Since this crate produces the same output as
+/// Sega's encoder, a `false` result means the payload was created by some other
+/// encoder. That includes payloads of data the stock encoder can't compress
+/// at all, as `Effort::Best` and other encoders may produce; only errors
+/// decompressing `blob` are returned.
+pub fn is_canonical(blob: &[u8], size: Size) -> Result<bool, CompressionError> {
+    let decompressed = decompress(blob, size)?;
+    match compress(&decompressed, size) {
+        Ok(recompressed) => Ok(recompressed == blob),
+        Err(CompressionError::EncoderFailed) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Re-encodes a header-prefixed CMP stream with this crate's encoder in its
+/// original width, returning the canonical stream along with whether it
+/// differs from `blob`.
+///
+/// This is `transcode` to the stream's own `Size`. The comparison covers the
+/// whole of `blob`, so a stream with a non-standard header or trailing data
+/// after its payload is reported as differing even if its commands don't.
+///
+/// The stock encoder can't compress some data at all; since it has no
+/// canonical encoding, `blob` is returned unchanged, along with `false`.
+pub fn canonicalize(blob: &[u8]) -> Result<(Vec<u8>, bool), CompressionError> {
+    let header = parse_header(blob)?;
+    let data = decompress_with_header(blob)?;
+    let canonical = match compress_with_header(&data, header.size) {
+        Ok(canonical) => canonical,
+        Err(CompressionError::EncoderFailed) => return Ok((blob.to_vec(), false)),
+        Err(e) => return Err(e),
+    };
+    let differed = canonical != blob;
+    Ok((canonical, differed))
+}
+
+/// Rewrites a headerless payload compressed in increments of `size` into the
+/// smallest stream of stock commands which decompresses to the same data.
+///
+/// The payload is decompressed and compressed again with `Effort::Best`, which
+/// weighs every choice of run and literal lengths rather than committing to
+/// each run as it's found, as Sega's encoder does. Since that finds the
+/// smallest encoding there is, the result is never longer than `blob`, though
+/// it may differ from it even when it's the same length. Any CMP decoder can
+/// read it.
+pub fn optimize(blob: &[u8], size: Size) -> Result<Vec<u8>, CompressionError> {
+    let data = decompress(blob, size)?;
+    let options = CompressOptions {
+        effort: Effort::Best,
+        ..CompressOptions::default()
+    };
+    compress_with_options(&data, size, options)
+}
+
+/// Rewrites the header of a stream whose payload is intact but whose declared
+/// size is wrong, returning a header with the size the payload actually
+/// decompresses to followed by the original payload.
+///
+/// The width is taken from the existing header, so its size byte must still be
+/// valid. Everything after the header is taken to be the payload, and is
+/// checked to be well-formed without being decompressed; trailing bytes which
+/// aren't a complete command are an error. If the corrupt size changed which
+/// header width `parse_header` detects - a 32-bit size whose upper half was
+/// zeroed, say - the payload is misplaced by four bytes and will usually fail
+/// to parse.
+pub fn repair_header(blob: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let header = parse_header(blob)?;
+    let payload = &blob[header.encoded_len()..];
+    let len = decompressed_len(payload, header.size)?;
+    let len = i32::try_from(len).map_err(|_| CompressionError::InputTooLarge)?;
+    let mut out = create_header(len, header.size);
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+/// Checks, without decompressing anything, that a payload of `payload_len`
+/// bytes could plausibly hold the data declared by the header at the start of
+/// `header`.
+///
+/// The shortest possible payload stores the whole declared size as runs as
+/// long as a command can hold; the longest stores every unit as a literal of
+/// its own, taking two units each. A length outside that range means the
+/// payload's extent has been worked out wrongly - a bad offset in a container,
+/// say - and is rejected with `CompressionError::ImplausiblePayloadLen`. A
+/// length inside it doesn't guarantee the payload is intact.
+pub fn check_payload_span(header: &[u8], payload_len: usize) -> Result<(), CompressionError> {
+    let header = parse_header(header)?;
+    let width = header.size.unit_len();
+    let declared = header.decompressed_size as usize;
+    let units = declared.div_ceil(width);
+    let min = units.div_ceil(max_run(header.size)) * 2 * width;
+    // The last unit may be padding which the header leaves out
+    let max = units * 2 * width;
+    if payload_len < min || payload_len > max {
+        return Err(CompressionError::ImplausiblePayloadLen {
+            len: payload_len,
+            min,
+            max,
+        });
+    }
+    Ok(())
+}
+
+/// A description of how a header-prefixed stream decodes, as returned by `diagnose`.
+#[derive(Debug)]
+pub struct Diagnostics {
+    /// The decompressed size declared by the header, or `None` if the header
+    /// couldn't be read.
+    pub declared_size: Option<usize>,
+    /// The number of bytes the stream produces before decoding stops; if the
+    /// last command overshoots the declared size, this includes all of it.
+    pub actual_size: usize,
+    /// The number of commands successfully decoded.
+    pub commands: usize,
+    /// The offset into the blob at which the stream stops agreeing with its
+    /// header: the start of the command which failed or overshot, or the end of
+    /// the blob if it ran out before producing the declared size. `None` when
+    /// the stream is consistent, or when the header itself couldn't be read.
+    /// `decompress_with_header` trims the output of an overshooting command,
+    /// so an overshoot sets this without setting `error`.
+    pub mismatch_offset: Option<usize>,
+    /// The error `decompress_with_header` would return for this blob, if any.
+    pub error: Option<CompressionError>,
+}
+
+impl Diagnostics {
+    /// Returns whether the blob decodes cleanly to its declared size.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Decodes a header-prefixed blob without keeping its output, reporting how
+/// far it got and where it went wrong instead of only whether it succeeded.
+///
+/// This follows the same rules as `decompress_with_header`, so `error` is set
+/// exactly when that function would fail.
+pub fn diagnose(blob: &[u8]) -> Diagnostics {
+    let mut diagnostics = Diagnostics {
+        declared_size: None,
+        actual_size: 0,
+        commands: 0,
+        mismatch_offset: None,
+        error: None,
+    };
+
+    let header = match parse_header(blob) {
+        Ok(header) => header,
+        Err(e) => {
+            diagnostics.error = Some(e);
+            return diagnostics;
+        }
+    };
+    let expected = header.decompressed_size as usize;
+    diagnostics.declared_size = Some(expected);
+
+    let offset = header.encoded_len();
+    let mut commands = Commands::new(&blob[offset..], header.size);
+    while diagnostics.actual_size < expected {
+        let command_offset = offset + commands.position();
+        let command = match commands.next() {
+            Some(Ok(command)) => command,
+            Some(Err(e)) => {
+                diagnostics.mismatch_offset = Some(command_offset);
+                diagnostics.error = Some(e);
+                return diagnostics;
+            }
+            None => break,
+        };
+        diagnostics.actual_size += command.decompressed_len();
+        diagnostics.commands += 1;
+        if diagnostics.actual_size > expected {
+            diagnostics.mismatch_offset = Some(command_offset);
+        }
+    }
+
+    if diagnostics.actual_size < expected {
+        diagnostics.mismatch_offset.get_or_insert(blob.len());
+        diagnostics.error = Some(CompressionError::SizeMismatch {
+            expected,
+            actual: diagnostics.actual_size,
+        });
+    }
+    diagnostics
+}
+
+/// Returns whether `data` appears to already be a header-prefixed CMP stream.
+///
+/// This is meant to catch compressed assets being fed back into the encoder by
+/// mistake, so it errs towards `false`: the header must be valid and declare a
+/// non-zero size, the payload must be no larger than that size (the stock
+/// encoder never expands data), and the payload must decode to exactly the
+/// declared size and end exactly at the end of `data`. Nothing is decompressed,
+/// so this is cheap even for large inputs.
+pub fn looks_like_cmp(data: &[u8]) -> bool {
+    let header = match parse_header(data) {
+        Ok(header) => header,
+        Err(_) => return false,
+    };
+    let expected = header.decompressed_size as usize;
+    let payload = &data[header.encoded_len()..];
+    if expected == 0 || payload.is_empty() || payload.len() > expected {
+        return false;
+    }
+
+    let mut produced = 0;
+    let mut commands = Commands::new(payload, header.size);
+    while produced < expected {
+        match commands.next() {
+            Some(Ok(command)) => produced += command.decompressed_len(),
+            _ => return false,
+        }
+    }
+    produced == expected && commands.position() == payload.len()
+}
+
+/// Returns whether the headerless payload `blob` is position-independent, so
+/// it can be moved to any suitably aligned address before it's decoded.
+///
+/// CMP has no commands which refer to an absolute address, or to earlier
+/// output: every command is either a run, which repeats the unit following its
+/// count, or a literal, which copies the units following its count. So any
+/// well-formed payload is position-independent, and this checks that every
+/// command in `blob` decodes in increments of `size` and that the last one
+/// ends exactly at the end of `blob`. Nothing is decompressed.
+pub fn is_position_independent(blob: &[u8], size: Size) -> bool {
+    let mut commands = Commands::new(blob, size);
+    while commands.position() < blob.len() {
+        if !matches!(commands.next(), Some(Ok(_))) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Checks that the headerless payload `blob`, compressed in increments of
+/// `size`, has no more than `max_commands` commands.
+///
+/// Some games' decoders keep a fixed-size command counter, or process
+/// commands into a fixed-size table, and break on payloads with more commands
+/// than that; the limit differs from game to game, so it's up to the caller.
+/// Returns `CompressionError::TooManyCommands` with the payload's actual
+/// command count if it's over the limit. The whole payload is checked to be
+/// well-formed, as with `decompressed_len`.
+pub fn check_decoder_limits(
+    blob: &[u8],
+    size: Size,
+    max_commands: usize,
+) -> Result<(), CompressionError> {
+    let mut commands = 0;
+    for command in Commands::new(blob, size) {
+        command?;
+        commands += 1;
+    }
+    if commands > max_commands {
+        return Err(CompressionError::TooManyCommands {
+            commands,
+            limit: max_commands,
+        });
+    }
+    Ok(())
+}
+
+/// Compresses `data` in increments of `size` `iterations` times, returning
+/// whether every run produced exactly the same bytes.
+///
+/// The encoder is expected to be deterministic - it keeps no state between
+/// calls, and only ever returns bytes it's written - so `false` would point to
+/// a bug such as uninitialized memory leaking into the output. Fewer than two
+/// iterations have nothing to compare, and always return `true` once `data`
+/// has compressed.
+pub fn is_deterministic(
+    data: &[u8],
+    size: Size,
+    iterations: usize,
+) -> Result<bool, CompressionError> {
+    let first = compress(data, size)?;
+    for _ in 1..iterations {
+        if compress(data, size)? != first {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the offset of the first byte at which `a` and `b` differ, or
+/// `None` if they're identical.
+///
+/// If one is a prefix of the other, they differ at the end of the shorter one.
+pub fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(x, y)| x != y)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+/// A single decoded command, as reported by `diff_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodedCommand {
+    /// `count` copies of `unit`.
+    Run { unit: Vec<u8>, count: usize },
+    /// Units copied directly to the output.
+    Literal { units: Vec<u8> },
+}
+
+impl DecodedCommand {
+    fn from_command(command: Command) -> DecodedCommand {
+        match command {
+            Command::Run { unit, count } => DecodedCommand::Run {
+                unit: unit.to_vec(),
+                count,
+            },
+            Command::Literal { units } => DecodedCommand::Literal {
+                units: units.to_vec(),
+            },
+        }
+    }
+}
+
+/// Where two headerless payloads first diverge, as returned by `diff_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffReport {
+    /// The offset of the first byte which differs, as with `first_difference`.
+    pub offset: usize,
+    /// The number of commands the payloads have in common before diverging.
+    pub command_index: usize,
+    /// The offset at which the command containing `offset` begins, which is
+    /// the same in both payloads.
+    pub command_offset: usize,
+    /// The command at `command_offset` in the first payload, or `None` if that
+    /// payload ends there or doesn't hold a complete command.
+    pub a: Option<DecodedCommand>,
+    /// The command at `command_offset` in the second payload.
+    pub b: Option<DecodedCommand>,
+}
+
+/// Compares two headerless payloads compressed in increments of `size`,
+/// reporting the first command at which they differ, or `None` if they're
+/// identical.
+///
+/// This is for tracking down where an encoder's output stops matching the
+/// stock encoder's: the payloads are decoded in step up to the first differing
+/// byte, and the command each one has there is decoded in full. Everything
+/// before that command is identical in both payloads, so it decodes the same
+/// way in each.
+pub fn diff_report(a: &[u8], b: &[u8], size: Size) -> Option<DiffReport> {
+    let offset = first_difference(a, b)?;
+
+    let mut command_index = 0;
+    let mut commands = Commands::new(a, size);
+    let mut command_offset = 0;
+    // Commands which end before the difference are shared by both payloads
+    while let Some(Ok(_)) = commands.next() {
+        if commands.position() > offset {
+            break;
+        }
+        command_offset = commands.position();
+        command_index += 1;
+    }
+
+    let decode = |payload: &[u8]| {
+        payload
+            .get(command_offset..)
+            .and_then(|rest| Commands::new(rest, size).next())
+            .and_then(Result::ok)
+            .map(DecodedCommand::from_command)
+    };
+    Some(DiffReport {
+        offset,
+        command_index,
+        command_offset,
+        a: decode(a),
+        b: decode(b),
+    })
+}
@@ -0,0 +1,59 @@
+use sega_cmp::{compress, CompressionError, Size};
+
+/// How `compress` should treat a buffer of zeros.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Compressed,
+    /// A single unit can't be compressed without expanding it
+    Incompressible,
+    Misaligned,
+}
+
+fn outcome(len: usize, size: Size) -> Outcome {
+    match compress(&vec![0; len], size) {
+        Ok(_) => Outcome::Compressed,
+        Err(CompressionError::EncoderFailed) => Outcome::Incompressible,
+        Err(CompressionError::Misaligned { width, remainder }) => {
+            let unit_len = match size {
+                Size::Byte => 1,
+                Size::Word => 2,
+                Size::Longword => 4,
+            };
+            assert_eq!((width, remainder), (unit_len, len % unit_len));
+            Outcome::Misaligned
+        }
+        Err(e) => panic!("unexpected error for {} bytes as {:?}: {}", len, size, e),
+    }
+}
+
+#[test]
+fn byte_accepts_every_length() {
+    for &len in [2, 4, 6, 8].iter() {
+        assert_eq!(outcome(len, Size::Byte), Outcome::Compressed, "{}", len);
+    }
+}
+
+#[test]
+fn word_accepts_multiples_of_two() {
+    assert_eq!(outcome(2, Size::Word), Outcome::Incompressible);
+    assert_eq!(outcome(4, Size::Word), Outcome::Compressed);
+    assert_eq!(outcome(6, Size::Word), Outcome::Compressed);
+    assert_eq!(outcome(8, Size::Word), Outcome::Compressed);
+}
+
+#[test]
+fn longword_rejects_multiples_of_two_which_arent_multiples_of_four() {
+    assert_eq!(outcome(2, Size::Longword), Outcome::Misaligned);
+    assert_eq!(outcome(4, Size::Longword), Outcome::Incompressible);
+    assert_eq!(outcome(6, Size::Longword), Outcome::Misaligned);
+    assert_eq!(outcome(8, Size::Longword), Outcome::Compressed);
+}
+
+#[test]
+fn misaligned_errors_say_how_far_over_the_buffer_is() {
+    let error = compress(&[0; 10], Size::Longword).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Provided buffer is not an even multiple of 32 bits (2 bytes over)"
+    );
+}
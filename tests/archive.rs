@@ -0,0 +1,30 @@
+use sega_cmp::{Archive, CompressionError, Size};
+
+#[test]
+fn archive_round_trips_through_bytes() {
+    let mut archive = Archive::new();
+    archive.add("text", &[0x41; 64], Size::Byte).unwrap();
+    archive
+        .add("tiles", &[0x12, 0x34].repeat(32), Size::Word)
+        .unwrap();
+
+    let archive = Archive::from_bytes(&archive.to_bytes()).unwrap();
+    assert_eq!(archive.names().collect::<Vec<_>>(), vec!["text", "tiles"]);
+    assert_eq!(archive.get("text").unwrap().unwrap(), vec![0x41; 64]);
+    assert_eq!(
+        archive.get("tiles").unwrap().unwrap(),
+        [0x12, 0x34].repeat(32)
+    );
+    assert!(archive.get("missing").is_none());
+}
+
+#[test]
+fn truncated_directory_is_rejected() {
+    let mut archive = Archive::new();
+    archive.add("text", &[0x41; 64], Size::Byte).unwrap();
+    let bytes = archive.to_bytes();
+    assert!(matches!(
+        Archive::from_bytes(&bytes[..10]),
+        Err(CompressionError::InvalidArchive)
+    ));
+}
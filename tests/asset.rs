@@ -0,0 +1,44 @@
+use sega_cmp::{compress_with_header, from_saturn_asset, to_saturn_asset, CompressionError, Size};
+
+#[test]
+fn asset_framing_precedes_a_stock_block() {
+    let data = [0x12, 0x34].repeat(32);
+    let asset = to_saturn_asset(&data, Size::Word).unwrap();
+    assert_eq!(&asset[..12], b"CMPS\x00\x01\x00\x00\x00\x00\x00\x40");
+    assert_eq!(
+        &asset[12..],
+        &compress_with_header(&data, Size::Word).unwrap()[..]
+    );
+    assert_eq!(from_saturn_asset(&asset).unwrap(), (data, Size::Word));
+}
+
+#[test]
+fn malformed_assets_are_rejected() {
+    let asset = to_saturn_asset(&[0x41; 64], Size::Byte).unwrap();
+    assert!(matches!(
+        from_saturn_asset(&asset[..8]),
+        Err(CompressionError::InvalidAsset)
+    ));
+
+    let mut wrong_magic = asset.clone();
+    wrong_magic[0] = b'X';
+    assert!(matches!(
+        from_saturn_asset(&wrong_magic),
+        Err(CompressionError::InvalidAsset)
+    ));
+
+    // The framing's size has to agree with the CMP header's
+    let mut wrong_size = asset.clone();
+    wrong_size[11] = 0x41;
+    assert!(matches!(
+        from_saturn_asset(&wrong_size),
+        Err(CompressionError::InvalidAsset)
+    ));
+
+    let mut wrong_width = asset;
+    wrong_width[5] = 2;
+    assert!(matches!(
+        from_saturn_asset(&wrong_width),
+        Err(CompressionError::InvalidAsset)
+    ));
+}
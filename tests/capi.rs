@@ -0,0 +1,69 @@
+#![cfg(feature = "capi")]
+
+use sega_cmp::capi::{cmp_compress_buffer, cmp_decompress, CMP_BUFFER_TOO_SMALL, CMP_OK};
+
+#[test]
+fn c_api_round_trips() {
+    let data = [0x41u8; 64];
+    let mut compressed = [0u8; 64];
+    let mut compressed_len = 0;
+    let status = unsafe {
+        cmp_compress_buffer(
+            data.as_ptr(),
+            data.len(),
+            0,
+            compressed.as_mut_ptr(),
+            compressed.len(),
+            &mut compressed_len,
+        )
+    };
+    assert_eq!(status, CMP_OK);
+
+    let mut out = [0u8; 16];
+    let mut out_len = 0;
+    let status = unsafe {
+        cmp_decompress(
+            compressed.as_ptr(),
+            compressed_len,
+            out.as_mut_ptr(),
+            out.len(),
+            &mut out_len,
+        )
+    };
+    assert_eq!(status, CMP_BUFFER_TOO_SMALL);
+    assert_eq!(out_len, 64);
+
+    let mut out = [0u8; 64];
+    let status = unsafe {
+        cmp_decompress(
+            compressed.as_ptr(),
+            compressed_len,
+            out.as_mut_ptr(),
+            out.len(),
+            &mut out_len,
+        )
+    };
+    assert_eq!(status, CMP_OK);
+    assert_eq!(&out[..out_len], &data[..]);
+}
+
+#[test]
+fn c_api_checks_the_declared_size_before_decoding() {
+    // A Longword header declaring nearly 4 GiB, then a single run command
+    let input = [
+        0x00, 0x0c, 0x00, 0x00, 0xff, 0xff, 0xff, 0xfc, 0x7f, 0xff, 0xff, 0xff,
+    ];
+    let mut out = [0u8; 16];
+    let mut out_len = 0;
+    let status = unsafe {
+        cmp_decompress(
+            input.as_ptr(),
+            input.len(),
+            out.as_mut_ptr(),
+            out.len(),
+            &mut out_len,
+        )
+    };
+    assert_eq!(status, CMP_BUFFER_TOO_SMALL);
+    assert_eq!(out_len, 0xffff_fffc);
+}
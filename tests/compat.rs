@@ -0,0 +1,34 @@
+use sega_cmp::{compatibility_info, Backend, HeaderWidth, Size};
+
+#[test]
+fn test_backend_matches_features() {
+    let expected = if cfg!(feature = "pure-rust") {
+        Backend::PureRust
+    } else if cfg!(feature = "system-libcmpr") {
+        Backend::SystemLibcmpr
+    } else {
+        Backend::BundledC
+    };
+    assert_eq!(compatibility_info().backend, expected);
+}
+
+#[test]
+fn test_reports_every_size_and_header_width() {
+    let info = compatibility_info();
+    assert_eq!(info.sizes, &[Size::Byte, Size::Word, Size::Longword]);
+    assert_eq!(
+        info.header_widths,
+        &[HeaderWidth::Bits16, HeaderWidth::Bits32]
+    );
+    assert_eq!(info.max_input_len, i32::MAX as usize);
+    assert_eq!(info.max_decompressed_size, u32::MAX);
+}
+
+#[test]
+fn test_only_the_bundled_encoder_reports_c_debug() {
+    let info = compatibility_info();
+    assert_eq!(
+        info.c_debug,
+        info.backend == Backend::BundledC && cfg!(feature = "c-debug")
+    );
+}
@@ -0,0 +1,1000 @@
+use sega_cmp::{
+    best_size, compare_modes, compress, compress_aligned_output, compress_aligned_prefix,
+    compress_all_modes, compress_any, compress_block, compress_buf, compress_checked,
+    compress_continuation, compress_deinterleaved, compress_delta, compress_detailed, compress_in,
+    compress_iter, compress_max_block, compress_or_passthrough, compress_padded_to,
+    compress_palette, compress_reader_with_hash, compress_rect, compress_segments,
+    compress_sequence, compress_units, compress_with_capacity_policy, compress_with_fallback,
+    compress_with_hash, compress_with_header, compress_with_options, compress_with_progress,
+    compress_with_tail, compress_within, compresses_identically, create_header, decompress,
+    decompress_deinterleaved, decompress_delta, decompress_sequence, decompress_with_header,
+    decompress_with_options, exact_sizes, export_run_stats, find_runs, max_compressed_len,
+    min_compressible_len, parse_header, run_savings_table, suggest_size, transcode,
+    write_compressed, CapacityPolicy, CompressOptions, CompressionError, Compressor, Size,
+};
+
+#[test]
+fn documented_example_compresses_to_pinned_bytes() {
+    // The crate docs describe this as `8A4B2A1B1A`. The real encoding is a run
+    // of 8 A (count 6), a run of 4 B (count 2), a run of 2 A (count 0), then a
+    // literal of 2 units (count -2) holding "BA".
+    assert_eq!(
+        compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap(),
+        vec![0x06, 0x41, 0x02, 0x42, 0x00, 0x41, 0xFE, 0x42, 0x41]
+    );
+}
+
+#[test]
+fn extended_runs_encode_a_cleared_tilemap_as_one_command() {
+    let options = CompressOptions {
+        extended_runs: true,
+        ..CompressOptions::default()
+    };
+    let data = vec![0u8; 200_000];
+    // Byte mode: count 0x7F, then 200000 - 129 extra repeats, then the byte
+    let compressed = compress_with_options(&data, Size::Byte, options).unwrap();
+    assert_eq!(compressed, vec![0x7F, 0x00, 0x03, 0x0C, 0xBF, 0x00]);
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        let compressed = compress_with_options(&data, size, options).unwrap();
+        assert!(compressed.len() <= 4 + 4 + 4);
+        assert_eq!(
+            decompress_with_options(&compressed, size, options).unwrap(),
+            data
+        );
+    }
+}
+
+#[test]
+fn compress_detailed_counts_commands() {
+    let (compressed, commands) = compress_detailed(b"AAAAAAAABBBBAABA", Size::Byte).unwrap();
+    assert_eq!(
+        compressed,
+        compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap()
+    );
+    assert_eq!(commands, 4);
+}
+
+#[test]
+fn misaligned_input_compresses_like_aligned_input() {
+    let mut data = vec![0xFF];
+    data.extend_from_slice(&[0x11; 16]);
+    data.extend_from_slice(&[0x22; 32]);
+    let aligned = data[1..].to_vec();
+    for &size in [Size::Word, Size::Longword].iter() {
+        assert_eq!(
+            compress(&data[1..], size).unwrap(),
+            compress(&aligned, size).unwrap()
+        );
+    }
+}
+
+#[test]
+fn transcode_changes_mode_and_header() {
+    let data = [[0x11u8; 8], [0x22; 8], [0x33; 8], [0x44; 8]].concat();
+    let word = compress_with_header(&data, Size::Word).unwrap();
+    assert_eq!(
+        transcode(&word, Size::Longword).unwrap(),
+        compress_with_header(&data, Size::Longword).unwrap()
+    );
+}
+
+#[test]
+fn compress_continuation_defers_a_run_into_the_next_fragment() {
+    let first = [&[b'A'; 8][..], &[b'Z'; 3]].concat();
+    let second = [b'Z'; 5];
+    let (compressed, deferred) = compress_continuation(&first, Size::Byte, Some(b"Z")).unwrap();
+    assert_eq!(deferred, 3);
+    assert_eq!(
+        compressed,
+        compress(&first[..first.len() - 3], Size::Byte).unwrap()
+    );
+
+    let next = [&first[first.len() - deferred..], &second[..]].concat();
+    let (next_compressed, _) = compress_continuation(&next, Size::Byte, None).unwrap();
+    assert_eq!(next_compressed, vec![0x06, b'Z']);
+}
+
+#[test]
+fn compress_continuation_can_defer_a_whole_fragment() {
+    let data = [b'Z'; 6];
+    let (compressed, deferred) = compress_continuation(&data, Size::Word, Some(b"ZZ")).unwrap();
+    assert_eq!(deferred, data.len());
+    assert!(compressed.is_empty());
+    assert_eq!(decompress(&compressed, Size::Word).unwrap(), vec![]);
+}
+
+#[test]
+fn find_runs_reports_maximal_runs() {
+    let runs = find_runs(b"AAAAAAAABBBBAABA", Size::Byte);
+    let found: Vec<_> = runs.iter().map(|r| (r.start, r.count)).collect();
+    assert_eq!(found, vec![(0, 8), (8, 4), (12, 2)]);
+
+    let runs = find_runs(&[1, 2, 1, 2, 1, 2, 3, 4], Size::Word);
+    assert_eq!(runs.len(), 1);
+    assert_eq!((runs[0].start, runs[0].unit_len, runs[0].count), (0, 2, 3));
+}
+
+#[test]
+fn export_run_stats_writes_a_row_per_run() {
+    let mut csv = Vec::new();
+    export_run_stats(b"AAAAAAAABBBBAABA", Size::Byte, &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "offset,run_length,value\n0,8,0x41\n8,4,0x42\n12,2,0x41\n"
+    );
+
+    let mut csv = Vec::new();
+    export_run_stats(&[1, 2, 1, 2, 1, 2, 3, 4], Size::Word, &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "offset,run_length,value\n0,3,0x0102\n"
+    );
+}
+
+#[test]
+fn export_run_stats_rejects_misaligned_data() {
+    let mut csv = Vec::new();
+    assert!(matches!(
+        export_run_stats(&[0; 6], Size::Longword, &mut csv),
+        Err(CompressionError::Misaligned { .. })
+    ));
+    assert!(csv.is_empty());
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn input_longer_than_c_int_is_rejected_in_every_mode() {
+    // Zeroed allocations are lazily mapped, so this doesn't touch 2 GiB of memory
+    let data = vec![0u8; i32::MAX as usize + 1];
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        assert!(matches!(
+            compress(&data, size),
+            Err(CompressionError::InputTooLarge)
+        ));
+    }
+}
+
+#[test]
+fn write_compressed_matches_compress_with_header() {
+    let data = [0x41; 64];
+    let mut out = Vec::new();
+    let written = write_compressed(&mut out, &data, Size::Byte).unwrap();
+    assert_eq!(written, out.len());
+    assert_eq!(out, compress_with_header(&data, Size::Byte).unwrap());
+}
+
+#[test]
+fn padded_output_fills_its_slot() {
+    let data = [0x41; 64];
+    let padded = compress_padded_to(&data, Size::Byte, 32).unwrap();
+    assert_eq!(padded.len(), 32);
+    assert_eq!(decompress_with_header(&padded).unwrap(), data.to_vec());
+    assert!(matches!(
+        compress_padded_to(&data, Size::Byte, 5),
+        Err(CompressionError::BudgetExceeded {
+            actual: 6,
+            budget: 5
+        })
+    ));
+}
+
+#[test]
+fn compress_buf_matches_compress() {
+    let data = [0x41; 64];
+    assert_eq!(
+        &*compress_buf(&data, Size::Byte).unwrap(),
+        &compress(&data, Size::Byte).unwrap()[..]
+    );
+}
+
+#[test]
+fn incompressible_input_fails_repeatedly_without_leaking() {
+    // The encoder allocates its output before it finds out the input expands,
+    // so each of these failures has a buffer to release
+    let mut state = 0x1234_5678u32;
+    let data: Vec<u8> = (0..4096)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    for _ in 0..1000 {
+        for &size in &[Size::Byte, Size::Word, Size::Longword] {
+            assert!(matches!(
+                compress_buf(&data, size),
+                Err(CompressionError::EncoderFailed)
+            ));
+        }
+    }
+}
+
+#[test]
+fn compress_palette_reports_colour_stats() {
+    let mut colors = vec![0x0000; 12];
+    colors.extend_from_slice(&[0x7FFF, 0x001F, 0x001F, 0x03E0]);
+    let (compressed, stats) = compress_palette(&colors).unwrap();
+    let bytes: Vec<u8> = colors.iter().flat_map(|c| c.to_be_bytes()).collect();
+    assert_eq!(compressed, compress(&bytes, Size::Word).unwrap());
+    assert_eq!(stats.distinct_colors, 4);
+    assert_eq!(stats.longest_run, 12);
+}
+
+#[test]
+fn suggested_size_matches_best_size_for_fixtures() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for entry in std::fs::read_dir(fixtures).unwrap() {
+        let data = std::fs::read(entry.unwrap().path()).unwrap();
+        assert_eq!(suggest_size(&data), best_size(&data).unwrap());
+    }
+}
+
+#[test]
+fn sequences_store_similar_frames_as_deltas() {
+    let first: Vec<u8> = (0..256)
+        .map(|i| if i < 128 { 0 } else { i as u8 })
+        .collect();
+    let mut second = first.clone();
+    second[200] ^= 0xFF;
+    let third = vec![0x41; 64];
+    let frames = [&first[..], &second[..], &third[..]];
+
+    let sequence = compress_sequence(&frames, Size::Byte).unwrap();
+    let key = compress_with_header(&first, Size::Byte).unwrap();
+    assert_eq!(&sequence[..4], &[0, 0, 0, 0]);
+    assert_eq!(&sequence[4..4 + key.len()], &key[..]);
+    let delta = compress_delta(&first, &second, Size::Byte).unwrap();
+    assert_eq!(&sequence[4 + key.len()..8 + key.len()], &[0, 0, 0, 1]);
+    assert_eq!(
+        &sequence[8 + key.len()..8 + key.len() + delta.len()],
+        &delta[..]
+    );
+    assert_eq!(&sequence[8 + key.len() + delta.len()..][..4], &[0, 0, 0, 0]);
+
+    assert_eq!(
+        decompress_sequence(&sequence).unwrap(),
+        vec![first, second, third]
+    );
+    assert!(decompress_sequence(&[]).unwrap().is_empty());
+    // A delta frame needs a frame before it
+    assert!(matches!(
+        decompress_sequence(&sequence[4 + key.len()..]),
+        Err(CompressionError::InvalidSequence)
+    ));
+}
+
+#[test]
+fn delta_round_trips_against_its_base() {
+    let base: Vec<u8> = (0..256).map(|i| i as u8).collect();
+    let mut target = base.clone();
+    target[100] ^= 0xFF;
+    let compressed = compress_delta(&base, &target, Size::Byte).unwrap();
+    assert!(compressed.len() < 16);
+    assert_eq!(decompress_delta(&base, &compressed).unwrap(), target);
+    assert!(matches!(
+        compress_delta(&base, &target[1..], Size::Byte),
+        Err(CompressionError::LengthMismatch {
+            expected: 256,
+            actual: 255
+        })
+    ));
+}
+
+#[test]
+fn literal_marker_follows_each_literal_count() {
+    let options = CompressOptions {
+        literal_marker: Some(0xA5),
+        ..CompressOptions::default()
+    };
+    assert_eq!(
+        compress_with_options(b"AAAAAAAABBBBAABA", Size::Byte, options).unwrap(),
+        vec![0x06, 0x41, 0x02, 0x42, 0x00, 0x41, 0xFE, 0xA5, 0x42, 0x41]
+    );
+    // In wider modes the marker is padded to a whole unit
+    assert_eq!(
+        compress_with_options(b"XYXYXYABCD", Size::Word, options).unwrap(),
+        vec![0x00, 0x01, 0x58, 0x59, 0xFF, 0xFE, 0xA5, 0x00, 0x41, 0x42, 0x43, 0x44]
+    );
+    assert_eq!(
+        compress_with_options(b"WXYZWXYZWXYZABCD", Size::Longword, options).unwrap(),
+        vec![
+            0x00, 0x00, 0x00, 0x01, 0x57, 0x58, 0x59, 0x5A, 0xFF, 0xFF, 0xFF, 0xFF, 0xA5, 0x00,
+            0x00, 0x00, 0x41, 0x42, 0x43, 0x44
+        ]
+    );
+    let data = [&b"ABCDEFGH"[..], &[0; 200_000], b"IJKL"].concat();
+    let extended = CompressOptions {
+        extended_runs: true,
+        ..options
+    };
+    for &options in [options, extended].iter() {
+        let compressed = compress_with_options(&data, Size::Word, options).unwrap();
+        assert_eq!(
+            decompress_with_options(&compressed, Size::Word, options).unwrap(),
+            data
+        );
+    }
+
+    let compressed = compress_with_options(b"AAAAB", Size::Byte, options).unwrap();
+    let other = CompressOptions {
+        literal_marker: Some(0x5A),
+        ..options
+    };
+    assert!(matches!(
+        decompress_with_options(&compressed, Size::Byte, other),
+        Err(CompressionError::UnexpectedLiteralMarker(0xA5))
+    ));
+}
+
+#[test]
+fn compresses_identically_compares_payloads() {
+    let a = [0x41; 64];
+    let mut b = a;
+    assert!(compresses_identically(&a, &b, Size::Byte).unwrap());
+    b[63] = 0x42;
+    assert!(!compresses_identically(&a, &b, Size::Byte).unwrap());
+    assert!(matches!(
+        compresses_identically(&a, b"ABCD", Size::Byte),
+        Err(CompressionError::EncoderFailed)
+    ));
+}
+
+#[test]
+fn max_run_splits_long_runs() {
+    let options = CompressOptions {
+        max_run: Some(4),
+        ..CompressOptions::default()
+    };
+    // 8 A as two runs of 4, then 5 B as a run of 3 and a run of 2
+    assert_eq!(
+        compress_with_options(b"AAAAAAAABBBBB", Size::Byte, options).unwrap(),
+        vec![0x02, 0x41, 0x02, 0x41, 0x01, 0x42, 0x00, 0x42]
+    );
+
+    let data = [&[0u8; 1000][..], b"ABCD", &[1; 300]].concat();
+    let extended = CompressOptions {
+        extended_runs: true,
+        max_run: Some(200),
+        ..CompressOptions::default()
+    };
+    for &options in [options, extended].iter() {
+        let compressed = compress_with_options(&data, Size::Byte, options).unwrap();
+        assert_eq!(
+            decompress_with_options(&compressed, Size::Byte, options).unwrap(),
+            data
+        );
+        assert_eq!(decompress(&compressed, Size::Byte).unwrap(), data);
+    }
+}
+
+#[test]
+fn compress_with_hash_returns_the_crc32_of_the_input() {
+    let data = [&b"123456789"[..], &[0; 64]].concat();
+    let (compressed, hash) = compress_with_hash(&data, Size::Byte).unwrap();
+    assert_eq!(compressed, compress(&data, Size::Byte).unwrap());
+    // As computed by zlib
+    assert_eq!(hash, 0xABDF_1D5B);
+}
+
+#[test]
+fn compress_reader_with_hash_matches_compress_with_hash() {
+    // Several read chunks long
+    let data: Vec<u8> = (0..300_000).map(|i| (i / 16) as u8).collect();
+    assert_eq!(
+        compress_reader_with_hash(&data[..], Size::Word).unwrap(),
+        compress_with_hash(&data, Size::Word).unwrap()
+    );
+}
+
+#[test]
+fn zero_filled_regions_use_the_fewest_possible_commands() {
+    let data = vec![0u8; 4 * 1024 * 1024];
+    // The longest run a single command can hold in each mode, in units
+    for &(size, unit_len, max_run) in [
+        (Size::Byte, 1, 129),
+        (Size::Word, 2, 32769),
+        (Size::Longword, 4, (1 << 31) + 1),
+    ]
+    .iter()
+    {
+        let (compressed, commands) = compress_detailed(&data, size).unwrap();
+        let units = data.len() / unit_len;
+        assert_eq!(commands, units.div_ceil(max_run));
+        // Each command is a count and a single unit
+        assert_eq!(compressed.len(), commands * unit_len * 2);
+        assert_eq!(decompress(&compressed, size).unwrap(), data);
+    }
+}
+
+#[test]
+fn compare_modes_reports_the_shorter_size() {
+    let comparison = compare_modes(&[0; 16], Size::Word, Size::Byte).unwrap();
+    assert_eq!(comparison.a.size, Size::Word);
+    assert_eq!(comparison.a.compressed_len, 4);
+    assert_eq!(comparison.a.commands, 1);
+    assert_eq!(comparison.b.compressed_len, 2);
+    assert_eq!(comparison.winner, Some(Size::Byte));
+    assert_eq!(comparison.delta, 2);
+    assert_eq!(
+        comparison.to_string(),
+        "Word: 4 bytes in 1 commands; Byte: 2 bytes in 1 commands; Byte is 2 bytes smaller"
+    );
+
+    let comparison = compare_modes(&[0; 16], Size::Byte, Size::Byte).unwrap();
+    assert_eq!(comparison.winner, None);
+    assert_eq!(comparison.delta, 0);
+    assert!(matches!(
+        compare_modes(&[0; 6], Size::Byte, Size::Longword),
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn compress_all_modes_matches_individual_calls() {
+    let data: Vec<u8> = (0..512 * 1024).map(|i| (i / 16) as u8).collect();
+    let modes = compress_all_modes(&data).unwrap();
+    for (mode, &size) in modes
+        .iter()
+        .zip([Size::Byte, Size::Word, Size::Longword].iter())
+    {
+        assert_eq!(mode.size, size);
+        assert_eq!(
+            mode.compressed.as_ref().unwrap(),
+            &compress(&data, size).unwrap()
+        );
+    }
+
+    // Literals and runs longer than a single command holds, runs of two inside
+    // and outside literals, and data which expands in every mode
+    let mut data: Vec<u8> = (0..300u32).map(|i| (i * 7) as u8).collect();
+    data.extend([0xAA; 2]);
+    data.extend((0..200u32).map(|i| i as u8));
+    data.extend([0x55; 70000]);
+    data.extend([1, 1, 2, 3, 3, 4, 5, 6]);
+    let expanding: Vec<u8> = (0..256u32).map(|i| i as u8).collect();
+    for data in [&data[..], &expanding, &[]].iter() {
+        let modes = compress_all_modes(data).unwrap();
+        for (mode, &size) in modes
+            .iter()
+            .zip([Size::Byte, Size::Word, Size::Longword].iter())
+        {
+            assert_eq!(
+                mode.compressed.as_ref().ok(),
+                compress(data, size).ok().as_ref(),
+                "{:?}",
+                size
+            );
+        }
+    }
+
+    let modes = compress_all_modes(&[0; 6]).unwrap();
+    assert_eq!(modes[1].compressed_len(), Some(4));
+    assert!(matches!(
+        modes[2].compressed,
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn compress_units_writes_units_big_endian() {
+    let words = [0x1234u16; 8];
+    let word_bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+    assert_eq!(
+        compress_units(&words).unwrap(),
+        compress(&word_bytes, Size::Word).unwrap()
+    );
+
+    let longwords = [0x1234_5678u32; 8];
+    let longword_bytes: Vec<u8> = longwords.iter().flat_map(|l| l.to_be_bytes()).collect();
+    assert_eq!(
+        compress_units(&longwords).unwrap(),
+        compress(&longword_bytes, Size::Longword).unwrap()
+    );
+
+    assert_eq!(
+        compress_units(&[0x41u8; 8]).unwrap(),
+        compress(&[0x41; 8], Size::Byte).unwrap()
+    );
+}
+
+#[test]
+fn max_compressed_len_allows_a_count_per_longest_literal() {
+    assert_eq!(max_compressed_len(0, Size::Byte), 0);
+    assert_eq!(max_compressed_len(128, Size::Byte), 129);
+    assert_eq!(max_compressed_len(129, Size::Byte), 131);
+    assert_eq!(max_compressed_len(65536, Size::Word), 65538);
+    assert_eq!(max_compressed_len(65538, Size::Word), 65542);
+    assert_eq!(max_compressed_len(8, Size::Longword), 12);
+}
+
+#[test]
+#[cfg(feature = "bytes")]
+fn compress_bytes_matches_compress() {
+    let data = [0x41; 64];
+    assert_eq!(
+        sega_cmp::compress_bytes(&data, Size::Byte).unwrap(),
+        compress(&data, Size::Byte).unwrap()
+    );
+}
+
+#[test]
+fn compress_checked_rejects_unexpected_lengths() {
+    let data = [0x41; 64];
+    assert_eq!(
+        compress_checked(&data, Size::Byte, 64).unwrap(),
+        compress(&data, Size::Byte).unwrap()
+    );
+    assert!(matches!(
+        compress_checked(&data[..60], Size::Byte, 64),
+        Err(CompressionError::LengthMismatch {
+            expected: 64,
+            actual: 60
+        })
+    ));
+}
+
+#[test]
+fn compress_aligned_prefix_leaves_the_tail_uncompressed() {
+    let data = [&[0x41; 16][..], &[1, 2, 3]].concat();
+    let (compressed, tail) = compress_aligned_prefix(&data, Size::Longword).unwrap();
+    assert_eq!(compressed, compress(&data[..16], Size::Longword).unwrap());
+    assert_eq!(tail, &[1, 2, 3]);
+
+    let (_, tail) = compress_aligned_prefix(&data[..16], Size::Word).unwrap();
+    assert!(tail.is_empty());
+}
+
+#[test]
+fn run_savings_table_shows_the_break_even_points() {
+    let table = run_savings_table(Size::Byte);
+    assert_eq!(table.len(), 261);
+    let saved = |len: usize| table[len - 1].1;
+    assert_eq!((saved(2), saved(3), saved(4)), (0, 0, 1));
+    assert_eq!((saved(129), saved(130), saved(131)), (126, 126, 126));
+    assert_eq!(saved(132), 127);
+
+    let table = run_savings_table(Size::Longword);
+    assert_eq!(table[3], (4, 4));
+}
+
+#[test]
+fn compress_max_block_bounds_every_block() {
+    let mut data = vec![0u8; 1000];
+    data.extend((0..200).map(|i| i as u8));
+    data.extend_from_slice(&[0x41; 300]);
+    for &max_len in [8, 9, 20, 64, 1000].iter() {
+        let blocks = compress_max_block(&data, Size::Byte, max_len).unwrap();
+        let mut out = Vec::new();
+        for block in &blocks {
+            assert!(block.len() <= max_len);
+            out.extend(decompress_with_header(block).unwrap());
+        }
+        assert_eq!(out, data);
+    }
+    assert_eq!(
+        compress_max_block(&[], Size::Byte, 4).unwrap(),
+        vec![vec![0, 0, 0, 0]]
+    );
+    assert!(matches!(
+        compress_max_block(&data, Size::Byte, 5),
+        Err(CompressionError::BudgetExceeded { .. })
+    ));
+}
+
+#[test]
+fn inputs_too_short_to_shrink_are_stored() {
+    assert_eq!(min_compressible_len(Size::Byte), 3);
+    assert_eq!(min_compressible_len(Size::Longword), 12);
+
+    // Two units is the longest input which can't shrink, even as a run
+    let packed = compress_or_passthrough("tiny", &[0; 4], Size::Word).unwrap();
+    assert!(!packed.is_compressed());
+    assert_eq!(packed.data(), &[0; 4]);
+
+    let packed = compress_or_passthrough("run", &[0; 6], Size::Word).unwrap();
+    assert!(packed.is_compressed());
+    assert_eq!(packed.data(), &[0, 1, 0, 0]);
+
+    assert!(matches!(
+        compress_or_passthrough("odd", &[0; 3], Size::Word),
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn compressed_blocks_round_trip() {
+    let data = b"AAAAAAAABBBBAABA";
+    let block = compress_block(data, Size::Byte).unwrap();
+    assert!(!block.stored);
+    assert_eq!(block.header, create_header(16, Size::Byte));
+    assert_eq!(block.payload, compress(data, Size::Byte).unwrap());
+
+    let mut written = Vec::new();
+    block.write_to(&mut written).unwrap();
+    assert_eq!(decompress_with_header(&written).unwrap(), data.to_vec());
+}
+
+#[test]
+fn incompressible_blocks_are_stored_behind_the_same_header() {
+    for data in [&b"ABCDEFGH"[..], b"AB"].iter() {
+        let block = compress_block(data, Size::Word).unwrap();
+        assert!(block.stored);
+        assert_eq!(block.payload, data.to_vec());
+
+        let mut written = Vec::new();
+        block.write_to(&mut written).unwrap();
+        // The header still describes the data, which follows it as-is
+        let header = parse_header(&written).unwrap();
+        assert_eq!(header.size, Size::Word);
+        assert_eq!(header.decompressed_size as usize, data.len());
+        assert_eq!(&written[header.encoded_len()..], *data);
+    }
+}
+
+#[test]
+fn compress_rect_gathers_rows_by_stride() {
+    // A 6x3 image, in which each of the rectangles below repeats a single unit
+    let image = b"aa0000aa0000ab0000";
+    assert_eq!(
+        compress_rect(image, 6, 2, 0, 4, 3, Size::Word).unwrap(),
+        compress(b"000000000000", Size::Word).unwrap()
+    );
+    assert_eq!(
+        compress_rect(image, 6, 0, 0, 2, 2, Size::Byte).unwrap(),
+        compress(b"aaaa", Size::Byte).unwrap()
+    );
+    assert_eq!(
+        compress_rect(image, 6, 6, 3, 0, 0, Size::Byte).unwrap(),
+        vec![]
+    );
+
+    assert!(matches!(
+        compress_rect(image, 6, 3, 0, 4, 1, Size::Byte),
+        Err(CompressionError::RegionOutOfBounds)
+    ));
+    assert!(matches!(
+        compress_rect(image, 6, 0, 2, 6, 2, Size::Byte),
+        Err(CompressionError::RegionOutOfBounds)
+    ));
+    assert!(matches!(
+        compress_rect(image, 6, 0, 0, 3, 1, Size::Word),
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn compress_with_fallback_narrows_misaligned_sizes() {
+    let data = [0; 6];
+    assert_eq!(
+        compress_with_fallback(&data, Size::Longword).unwrap(),
+        (compress(&data, Size::Word).unwrap(), Size::Word)
+    );
+    assert_eq!(
+        compress_with_fallback(&data[..5], Size::Longword).unwrap(),
+        (compress(&data[..5], Size::Byte).unwrap(), Size::Byte)
+    );
+    assert_eq!(
+        compress_with_fallback(&[0; 8], Size::Longword).unwrap().1,
+        Size::Longword
+    );
+    // Only misaligned input falls back
+    assert!(matches!(
+        compress_with_fallback(b"ABCD", Size::Byte),
+        Err(CompressionError::EncoderFailed)
+    ));
+}
+
+#[test]
+fn compress_iter_matches_compressing_the_collected_bytes() {
+    // A procedurally generated tilemap: rows of one tile index, as words
+    let tiles = (0..32u16).flat_map(|row| (0..32).flat_map(move |_| (row / 4).to_be_bytes()));
+    let collected: Vec<u8> = tiles.clone().collect();
+    assert_eq!(
+        compress_iter(tiles, Size::Word).unwrap(),
+        compress(&collected, Size::Word).unwrap()
+    );
+    assert!(matches!(
+        compress_iter(std::iter::repeat_n(0, 3), Size::Word),
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn capacity_policy_reserves_room_after_the_payload() {
+    let data = [0x41; 64];
+    let exact = compress_with_capacity_policy(&data, Size::Byte, CapacityPolicy::Exact).unwrap();
+    assert_eq!(exact, compress(&data, Size::Byte).unwrap());
+    assert_eq!(CapacityPolicy::default(), CapacityPolicy::Exact);
+
+    let reserved =
+        compress_with_capacity_policy(&data, Size::Byte, CapacityPolicy::Reserve(16)).unwrap();
+    assert_eq!(reserved, exact);
+    assert!(reserved.capacity() >= reserved.len() + 16);
+}
+
+#[test]
+fn compress_in_writes_to_a_caller_buffer() {
+    let data: Vec<u8> = (0..600u32)
+        .map(|i| if i % 50 < 30 { 0 } else { i as u8 })
+        .collect();
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        let expected = compress(&data, size).unwrap();
+        let mut out = vec![0; max_compressed_len(data.len(), size)];
+        let written = compress_in(&data, size, &mut out).unwrap();
+        assert_eq!(&out[..written], &expected[..]);
+
+        let mut short = vec![0; expected.len() - 1];
+        assert!(matches!(
+            compress_in(&data, size, &mut short),
+            Err(CompressionError::ScratchTooSmall { needed }) if needed == expected.len()
+        ));
+    }
+    assert_eq!(compress_in(&[], Size::Byte, &mut []).unwrap(), 0);
+}
+
+#[test]
+fn exact_sizes_match_compressed_lengths() {
+    let inputs: Vec<Vec<u8>> = vec![
+        (0..512 * 3).map(|i| (i / 16) as u8).collect(),
+        (0..600u32)
+            .map(|i| if i % 50 < 30 { 0 } else { i as u8 })
+            .collect(),
+        vec![0; 6],
+        b"ABCD".to_vec(),
+        vec![],
+    ];
+    for data in &inputs {
+        let sizes = exact_sizes(data).unwrap();
+        for (&size, &len) in [Size::Byte, Size::Word, Size::Longword].iter().zip(&sizes) {
+            assert_eq!(
+                len,
+                compress(data, size).ok().map(|c| c.len()),
+                "{:?}",
+                size
+            );
+        }
+    }
+    assert_eq!(exact_sizes(&[0; 6]).unwrap(), [Some(2), Some(4), None]);
+}
+
+#[test]
+fn deinterleaving_separates_planes_before_compressing() {
+    // Four planes, each a single repeated byte once separated
+    let data: Vec<u8> = (0..64).flat_map(|_| [0x00, 0xFF, 0x0F, 0xF0]).collect();
+    let blob = compress_deinterleaved(&data, 4, Size::Byte).unwrap();
+    assert_eq!(&blob[..4], &[0, 0, 0, 4]);
+    let planar: Vec<u8> = [0x00, 0xFF, 0x0F, 0xF0]
+        .iter()
+        .flat_map(|&byte| vec![byte; 64])
+        .collect();
+    assert_eq!(
+        &blob[4..],
+        &compress_with_header(&planar, Size::Byte).unwrap()[..]
+    );
+    assert!(blob.len() < compress_with_header(&data, Size::Byte).map_or(usize::MAX, |b| b.len()));
+    assert_eq!(decompress_deinterleaved(&blob).unwrap(), data);
+
+    let single = compress_deinterleaved(&[0; 8], 1, Size::Word).unwrap();
+    assert_eq!(decompress_deinterleaved(&single).unwrap(), vec![0; 8]);
+    assert!(matches!(
+        compress_deinterleaved(&[0; 10], 4, Size::Byte),
+        Err(CompressionError::Misaligned {
+            width: 4,
+            remainder: 2
+        })
+    ));
+    assert!(matches!(
+        decompress_deinterleaved(&[0, 0]),
+        Err(CompressionError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn aligned_output_is_padded_without_changing_what_it_decodes_to() {
+    let data = b"AAAA";
+    let blob = compress_with_header(data, Size::Byte).unwrap();
+    assert_eq!(blob.len(), 6);
+
+    let (padded, len) = compress_aligned_output(data, Size::Byte, 4).unwrap();
+    assert_eq!(len, 6);
+    assert_eq!(&padded[..len], &blob[..]);
+    assert_eq!(&padded[len..], &[0, 0]);
+    assert_eq!(decompress_with_header(&padded).unwrap(), data.to_vec());
+
+    // Longword output is already longword-aligned
+    let (padded, len) = compress_aligned_output(&[0; 16], Size::Longword, 4).unwrap();
+    assert_eq!(padded.len(), len);
+}
+
+#[test]
+fn segments_compress_like_their_concatenation() {
+    let cases: Vec<(Size, Vec<&[u8]>)> = vec![
+        // A run which starts in one segment and ends in the next
+        (Size::Byte, vec![b"ABCDAAAA", b"AAAAEFGH"]),
+        (Size::Word, vec![b"ABCDXYXY", b"XYXYEF"]),
+        // Empty segments at either end and in the middle
+        (Size::Byte, vec![b"", b"AAAAB", b"", b"BBBC", b""]),
+        // Segments which split units, though the total is a whole number of them
+        (Size::Word, vec![b"XYX", b"YXYX", b"YAB"]),
+        (Size::Longword, vec![b"WXYZW", b"XY", b"ZWXYZABCD"]),
+    ];
+    for (size, segments) in cases {
+        assert_eq!(
+            compress_segments(&segments, size).unwrap(),
+            compress(&segments.concat(), size).unwrap(),
+            "{:?} in {:?} mode",
+            segments,
+            size
+        );
+    }
+}
+
+#[test]
+fn segments_need_only_a_whole_number_of_units_in_total() {
+    assert!(matches!(
+        compress_segments(&[b"ABC", b"DE"], Size::Word),
+        Err(CompressionError::Misaligned {
+            width: 2,
+            remainder: 1
+        })
+    ));
+}
+
+#[test]
+fn compressor_round_trips_inputs_of_different_sizes() {
+    let large: Vec<u8> = (0..4096u32)
+        .map(|i| if i % 64 < 40 { 0 } else { i as u8 })
+        .collect();
+    let small = b"AAAAAAAABBBBAABA".to_vec();
+    let inputs = [
+        (&large, Size::Longword),
+        (&small, Size::Byte),
+        (&large, Size::Word),
+        (&small, Size::Word),
+        (&large, Size::Byte),
+    ];
+    let mut compressor = Compressor::new();
+    for &(data, size) in inputs.iter() {
+        let compressed = compressor.compress(data, size).unwrap().to_vec();
+        assert_eq!(compressed, compress(data, size).unwrap(), "{:?}", size);
+        assert_eq!(&decompress(&compressed, size).unwrap(), data);
+    }
+}
+
+#[test]
+fn compressor_can_be_reused_after_a_failure() {
+    let mut compressor = Compressor::with_capacity(64);
+    assert!(matches!(
+        compressor.compress(b"ABCD", Size::Byte),
+        Err(CompressionError::EncoderFailed)
+    ));
+    assert!(matches!(
+        compressor.compress(b"ABC", Size::Word),
+        Err(CompressionError::Misaligned { .. })
+    ));
+    // Nothing from the failed attempt is left in the output
+    assert_eq!(
+        compressor.compress(&[0x41; 4], Size::Byte).unwrap(),
+        &[0x02, 0x41]
+    );
+}
+
+#[test]
+fn progress_is_reported_in_order_up_to_the_input_length() {
+    let data: Vec<u8> = (0..300_000u32)
+        .map(|i| if i % 64 < 40 { 0 } else { i as u8 })
+        .collect();
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        let mut calls = Vec::new();
+        let compressed = compress_with_progress(&data, size, |processed, total| {
+            assert_eq!(total, data.len());
+            calls.push(processed);
+        })
+        .unwrap();
+        assert_eq!(compressed, compress(&data, size).unwrap());
+
+        assert_eq!(calls.first(), Some(&0));
+        assert_eq!(calls.last(), Some(&data.len()));
+        assert!(
+            calls.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{:?}",
+            calls
+        );
+        // Reported along the way, not just at the start and end
+        assert!(calls.len() > 3, "{:?}", calls);
+    }
+}
+
+#[test]
+fn progress_stops_after_a_failure() {
+    let mut calls = Vec::new();
+    assert!(matches!(
+        compress_with_progress(b"ABCD", Size::Byte, |processed, _| calls.push(processed)),
+        Err(CompressionError::EncoderFailed)
+    ));
+    assert_eq!(calls, vec![0]);
+}
+
+/// Decodes `compress_with_tail`'s output, as a decoder which knows about the
+/// final byte-mode literal would.
+fn decompress_with_tail(compressed: &[u8], size: Size, tail_len: usize) -> Vec<u8> {
+    if tail_len == 0 {
+        return decompress(compressed, size).unwrap();
+    }
+    let (body, tail) = compressed.split_at(compressed.len() - tail_len - 1);
+    assert_eq!(tail[0], -(tail_len as i8) as u8);
+    let mut out = decompress(body, size).unwrap();
+    out.extend_from_slice(&tail[1..]);
+    out
+}
+
+#[test]
+fn tails_shorter_than_a_unit_round_trip() {
+    for &(size, width) in [(Size::Word, 2), (Size::Longword, 4)].iter() {
+        for tail_len in 0..width {
+            let mut data = vec![0x41; 64];
+            data.extend((0..tail_len).map(|i| i as u8 + 1));
+            let (compressed, tail) = compress_with_tail(&data, size).unwrap();
+            assert_eq!(tail, tail_len);
+            assert_eq!(
+                decompress_with_tail(&compressed, size, tail),
+                data,
+                "{} byte tail in {:?} mode",
+                tail_len,
+                size
+            );
+        }
+    }
+    // With no tail, the stream is exactly `compress`'s
+    let data = [0x41; 64];
+    assert_eq!(
+        compress_with_tail(&data, Size::Longword).unwrap(),
+        (compress(&data, Size::Longword).unwrap(), 0)
+    );
+}
+
+#[test]
+fn compress_within_accepts_output_up_to_the_budget() {
+    let data = b"AAAAAAAABBBBAABA";
+    let expected = compress(data, Size::Byte).unwrap();
+    assert_eq!(expected.len(), 9);
+    assert_eq!(compress_within(data, Size::Byte, 9).unwrap(), expected);
+    assert_eq!(compress_within(data, Size::Byte, 100).unwrap(), expected);
+}
+
+#[test]
+fn compress_within_rejects_output_over_the_budget() {
+    assert!(matches!(
+        compress_within(b"AAAAAAAABBBBAABA", Size::Byte, 8),
+        Err(CompressionError::BudgetExceeded {
+            actual: 9,
+            budget: 8
+        })
+    ));
+    // Errors from compressing come first
+    assert!(matches!(
+        compress_within(b"ABC", Size::Word, 100),
+        Err(CompressionError::Misaligned { .. })
+    ));
+}
+
+#[test]
+fn compress_any_accepts_anything_viewable_as_bytes() {
+    let text = "AAAAAAAABBBBAABA";
+    let expected = compress(text.as_bytes(), Size::Byte).unwrap();
+    let string = String::from(text);
+    let bytes = text.as_bytes().to_vec();
+    let array: [u8; 16] = *b"AAAAAAAABBBBAABA";
+
+    assert_eq!(compress_any(text, Size::Byte).unwrap(), expected);
+    assert_eq!(compress_any(string, Size::Byte).unwrap(), expected);
+    assert_eq!(compress_any(&bytes[..], Size::Byte).unwrap(), expected);
+    assert_eq!(compress_any(&bytes, Size::Byte).unwrap(), expected);
+    assert_eq!(compress_any(bytes.clone(), Size::Byte).unwrap(), expected);
+    assert_eq!(
+        compress_any(bytes.into_boxed_slice(), Size::Byte).unwrap(),
+        expected
+    );
+    assert_eq!(compress_any(array, Size::Byte).unwrap(), expected);
+}
@@ -0,0 +1,394 @@
+use sega_cmp::{
+    compress, compress_with_header, concat_blocks, create_header, decompress, decompress_each,
+    decompress_to, decompress_with_header, decompress_with_options, total_decompressed_size,
+    BlockIter, ChunkedDecoder, CmpBlock, CmpCursor, CompressOptions, CompressionError, Size,
+};
+
+#[test]
+fn literal_longer_than_remaining_data_is_truncated() {
+    // Claims a literal of 5 bytes, but only 2 follow
+    let result = decompress(&[0xFB, 0x01, 0x02], Size::Byte);
+    assert!(matches!(result, Err(CompressionError::Truncated)));
+}
+
+#[test]
+fn run_without_pattern_is_truncated() {
+    let result = decompress(&[0x00, 0x05], Size::Word);
+    assert!(matches!(result, Err(CompressionError::Truncated)));
+}
+
+#[test]
+fn partial_count_is_truncated() {
+    let result = decompress(&[0x00, 0x02, 0x41, 0x41, 0xFF], Size::Word);
+    assert!(matches!(result, Err(CompressionError::Truncated)));
+}
+
+#[test]
+fn huge_literal_count_is_truncated() {
+    let result = decompress(
+        &[0x80, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04],
+        Size::Longword,
+    );
+    assert!(matches!(result, Err(CompressionError::Truncated)));
+}
+
+#[test]
+fn header_larger_than_payload_is_rejected() {
+    // Header declares 16 bytes; the payload is a run of 4
+    let result = decompress_with_header(&[0x00, 0x00, 0x00, 0x10, 0x02, 0x41]);
+    assert!(matches!(
+        result,
+        Err(CompressionError::SizeMismatch {
+            expected: 16,
+            actual: 4
+        })
+    ));
+}
+
+#[test]
+fn truncated_header_is_rejected() {
+    let result = decompress_with_header(&[0x00, 0x00, 0x00]);
+    assert!(matches!(result, Err(CompressionError::InvalidHeader)));
+}
+
+#[test]
+fn output_limit_stops_a_run_before_it_is_expanded() {
+    // A single longword run of 2^31 + 1 units, which would be 8 GiB
+    let payload = [0x7F, 0xFF, 0xFF, 0xFF, 0x12, 0x34, 0x56, 0x78];
+    let options = CompressOptions {
+        max_output: Some(1 << 20),
+        ..CompressOptions::default()
+    };
+    assert!(matches!(
+        decompress_with_options(&payload, Size::Longword, options),
+        Err(CompressionError::OutputLimitExceeded { limit: 0x100000 })
+    ));
+
+    // Output up to the limit is still produced
+    let payload = [0x06, 0x41, 0x02, 0x42, 0x00, 0x41, 0xFE, 0x42, 0x41];
+    let exact = CompressOptions {
+        max_output: Some(16),
+        ..CompressOptions::default()
+    };
+    assert_eq!(
+        decompress_with_options(&payload, Size::Byte, exact).unwrap(),
+        b"AAAAAAAABBBBAABA".to_vec()
+    );
+    let short = CompressOptions {
+        max_output: Some(15),
+        ..CompressOptions::default()
+    };
+    assert!(matches!(
+        decompress_with_options(&payload, Size::Byte, short),
+        Err(CompressionError::OutputLimitExceeded { limit: 15 })
+    ));
+}
+
+#[test]
+fn header_declaring_more_than_its_payload_holds_is_rejected() {
+    // A 32-bit header declaring 4 GiB, with no payload to back it up
+    let header = [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF];
+    assert!(matches!(
+        decompress_with_header(&header),
+        Err(CompressionError::SizeMismatch {
+            expected: 0xFFFF_FFFF,
+            actual: 0
+        })
+    ));
+}
+
+#[test]
+fn valid_payload_decompresses() {
+    let payload = [0x06, 0x41, 0x02, 0x42, 0x00, 0x41, 0xFE, 0x42, 0x41];
+    assert_eq!(
+        decompress(&payload, Size::Byte).unwrap(),
+        b"AAAAAAAABBBBAABA".to_vec()
+    );
+}
+
+#[test]
+fn block_iter_walks_consecutive_blocks() {
+    let data = [
+        0x00, 0x00, 0x00, 0x04, 0x02, 0x41, // AAAA
+        0x00, 0x00, 0x00, 0x04, 0xFC, 0x42, 0x43, 0x44, 0x45, // BCDE
+    ];
+    let blocks = BlockIter::new(&data)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks, vec![b"AAAA".to_vec(), b"BCDE".to_vec()]);
+}
+
+#[test]
+fn block_iter_stops_after_malformed_block() {
+    let data = [0x00, 0x00, 0x00, 0x04, 0x02, 0x41, 0x01, 0x02];
+    let mut blocks = BlockIter::new(&data);
+    assert_eq!(blocks.next().unwrap().unwrap(), b"AAAA".to_vec());
+    assert!(matches!(
+        blocks.next(),
+        Some(Err(CompressionError::InvalidHeader))
+    ));
+    assert!(blocks.next().is_none());
+}
+
+#[test]
+fn block_iter_reads_an_empty_block_before_a_word_block() {
+    // The empty block's header and the next one's also read as a single
+    // 32-bit header declaring 0x40008 bytes
+    let mut data = create_header(0, Size::Byte);
+    data.extend(compress_with_header(&[0x41; 8], Size::Word).unwrap());
+    let blocks = BlockIter::new(&data)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks, vec![vec![], vec![0x41; 8]]);
+    assert_eq!(total_decompressed_size(&data).unwrap(), 8);
+}
+
+#[test]
+fn concatenated_blocks_can_be_walked() {
+    let a = [0x00, 0x00, 0x00, 0x04, 0x02, 0x41];
+    let b = [0x00, 0x00, 0x00, 0x04, 0xFC, 0x42, 0x43, 0x44, 0x45];
+    let (joined, offset) = concat_blocks(&a, &b).unwrap();
+    assert_eq!(offset, a.len());
+    let blocks = BlockIter::new(&joined)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks, vec![b"AAAA".to_vec(), b"BCDE".to_vec()]);
+}
+
+#[test]
+fn an_empty_block_can_be_concatenated_with_a_wider_one() {
+    let a = create_header(0, Size::Byte);
+    for &size in &[Size::Word, Size::Longword] {
+        let b = compress_with_header(&[0x41; 16], size).unwrap();
+        let (joined, offset) = concat_blocks(&a, &b).unwrap();
+        assert_eq!(offset, 4);
+        let blocks = BlockIter::new(&joined)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(blocks, vec![vec![], vec![0x41; 16]]);
+    }
+}
+
+#[test]
+fn padded_block_cannot_be_concatenated() {
+    let a = [0x00, 0x00, 0x00, 0x04, 0x02, 0x41, 0x00, 0x00];
+    let b = [0x00, 0x00, 0x00, 0x04, 0x02, 0x41];
+    assert!(matches!(
+        concat_blocks(&a, &b),
+        Err(CompressionError::TrailingData(2))
+    ));
+}
+
+#[test]
+fn output_past_the_declared_size_is_trimmed() {
+    // Six bytes padded to two longwords, with a header declaring the original six
+    let data = [0x41; 6];
+    let mut padded = data.to_vec();
+    padded.resize(8, 0x41);
+    let mut blob = create_header(6, Size::Longword);
+    blob.extend(compress(&padded, Size::Longword).unwrap());
+    assert_eq!(decompress_with_header(&blob).unwrap(), data.to_vec());
+}
+
+#[test]
+fn chunked_decoder_splits_output_across_commands() {
+    let data = [&[0x1234u16.to_be_bytes(); 40].concat()[..], b"ABCDEFG!"].concat();
+    let compressed = compress(&data, Size::Word).unwrap();
+    for &chunk_len in [1, 3, 7, 64, 1000].iter() {
+        let mut decoder = ChunkedDecoder::new(&compressed, Size::Word, chunk_len);
+        let mut out = Vec::new();
+        while let Some(chunk) = decoder.next_chunk() {
+            let chunk = chunk.unwrap();
+            assert!(chunk.len() == chunk_len || out.len() + chunk.len() == data.len());
+            out.extend_from_slice(chunk);
+        }
+        assert_eq!(out, data);
+    }
+}
+
+#[test]
+fn chunked_decoder_reports_malformed_payloads() {
+    let mut decoder = ChunkedDecoder::new(&[0x00, 0x41, 0xFB, 0x42], Size::Byte, 1);
+    assert_eq!(decoder.next_chunk().unwrap().unwrap(), b"A");
+    assert_eq!(decoder.next_chunk().unwrap().unwrap(), b"A");
+    assert!(matches!(
+        decoder.next_chunk(),
+        Some(Err(CompressionError::Truncated))
+    ));
+    assert!(decoder.next_chunk().is_none());
+}
+
+#[test]
+fn cmp_block_reads_the_header_before_decompressing() {
+    let data = [0x41; 300];
+    let blob = compress_with_header(&data, Size::Byte).unwrap();
+    let block = CmpBlock::parse(&blob).unwrap();
+    assert_eq!(block.size(), Size::Byte);
+    assert_eq!(block.decompressed_len(), 300);
+    assert_eq!(block.payload(), &blob[4..]);
+    assert_eq!(block.decompress().unwrap(), data.to_vec());
+
+    // A bad payload only shows up once the block is decompressed
+    let block = CmpBlock::parse(&[0x00, 0x00, 0x00, 0x10, 0xFB]).unwrap();
+    assert!(block.decompress().is_err());
+}
+
+#[test]
+fn decompress_to_writes_everything_it_decodes() {
+    let data = [&[0x41; 10_000][..], b"BCDE"].concat();
+    let compressed = compress(&data, Size::Byte).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(
+        decompress_to(&compressed, Size::Byte, &mut out).unwrap(),
+        data.len()
+    );
+    assert_eq!(out, data);
+
+    let mut full = [0u8; 8];
+    assert!(matches!(
+        decompress_to(&compressed, Size::Byte, &mut &mut full[..]),
+        Err(CompressionError::Io(_))
+    ));
+}
+
+#[test]
+fn header_records_the_width_to_decode_in() {
+    let data = [0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01];
+    let blob = compress_with_header(&data, Size::Word).unwrap();
+    assert_eq!(decompress_with_header(&blob).unwrap(), data.to_vec());
+
+    // The bare payload decodes without complaint in the wrong width
+    let payload = &blob[4..];
+    assert_eq!(payload, &[0x00, 0x02, 0x00, 0x01]);
+    assert_eq!(
+        decompress(payload, Size::Byte).unwrap(),
+        vec![0x02, 0x02, 0x01, 0x01]
+    );
+}
+
+#[test]
+fn total_decompressed_size_sums_every_block() {
+    let small = compress_with_header(&[0x41; 100], Size::Byte).unwrap();
+    // Too large for a 16-bit header, so this one has a 32-bit header
+    let large = compress_with_header(&[0; 70000], Size::Word).unwrap();
+    let blocks = [small.clone(), large, small].concat();
+    assert_eq!(total_decompressed_size(&blocks).unwrap(), 70200);
+    assert_eq!(total_decompressed_size(&[]).unwrap(), 0);
+
+    assert!(matches!(
+        total_decompressed_size(&blocks[..blocks.len() - 1]),
+        Err(CompressionError::Truncated)
+    ));
+}
+
+#[test]
+fn decompress_each_passes_output_a_command_at_a_time() {
+    // A longword run of 1300 units, split across two chunks, then a literal
+    let mut data = [0x12, 0x34, 0x56, 0x78].repeat(1300);
+    data.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    let compressed = compress(&data, Size::Longword).unwrap();
+
+    let mut chunks = Vec::new();
+    let mut out = Vec::new();
+    let written = decompress_each(&compressed, Size::Longword, |chunk| {
+        chunks.push(chunk.len());
+        out.extend_from_slice(chunk);
+    })
+    .unwrap();
+    assert_eq!(chunks, vec![4096, 1104, 8]);
+    assert_eq!(written, data.len());
+    assert_eq!(out, data);
+
+    // Output before a truncated command has already been passed on
+    let mut seen = 0;
+    let result = decompress_each(&[0x00, 0x41, 0x05], Size::Byte, |chunk| seen += chunk.len());
+    assert!(matches!(result, Err(CompressionError::Truncated)));
+    assert_eq!(seen, 2);
+}
+
+/// Runs of each width alternating with literals, so seeks land in both.
+fn cursor_data() -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..20u8 {
+        data.extend(std::iter::repeat_n(i, 37));
+        data.extend_from_slice(&[i, i.wrapping_mul(3), i ^ 0x55, 0xFF]);
+    }
+    data
+}
+
+fn cursors(compressed: &[u8]) -> Vec<CmpCursor<'_>> {
+    vec![
+        CmpCursor::new(compressed).unwrap(),
+        CmpCursor::redecoding(compressed).unwrap(),
+    ]
+}
+
+#[test]
+fn cursor_reads_whole_stream() {
+    use std::io::Read;
+
+    let data = cursor_data();
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        let compressed = compress_with_header(&data, size).unwrap();
+        for mut cursor in cursors(&compressed) {
+            assert_eq!(cursor.decompressed_len(), data.len());
+            let mut out = Vec::new();
+            cursor.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+}
+
+#[test]
+fn cursor_seeks_to_decompressed_offsets() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let data = cursor_data();
+    let compressed = compress_with_header(&data, Size::Word).unwrap();
+    for mut cursor in cursors(&compressed) {
+        // Forward, backward, and into the middle of runs and literals
+        for &offset in [500usize, 3, 0, 38, 39, 700, 41, 819].iter() {
+            assert_eq!(
+                cursor.seek(SeekFrom::Start(offset as u64)).unwrap(),
+                offset as u64
+            );
+            let mut buf = [0; 9];
+            let n = cursor.read(&mut buf).unwrap();
+            assert!(n > 0);
+            assert_eq!(&buf[..n], &data[offset..offset + n]);
+            assert_eq!(cursor.stream_position().unwrap(), (offset + n) as u64);
+        }
+
+        cursor.seek(SeekFrom::End(-5)).unwrap();
+        let mut tail = Vec::new();
+        cursor.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &data[data.len() - 5..]);
+
+        cursor.seek(SeekFrom::Current(-10)).unwrap();
+        let mut buf = [0; 4];
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[data.len() - 10..data.len() - 6]);
+    }
+}
+
+#[test]
+fn cursor_reads_nothing_past_the_end() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let data = cursor_data();
+    let compressed = compress_with_header(&data, Size::Byte).unwrap();
+    for mut cursor in cursors(&compressed) {
+        cursor.seek(SeekFrom::End(10)).unwrap();
+        assert_eq!(cursor.read(&mut [0; 4]).unwrap(), 0);
+        assert!(cursor.seek(SeekFrom::Current(-10_000)).is_err());
+    }
+}
+
+#[test]
+fn cursor_rejects_malformed_streams() {
+    let data = cursor_data();
+    let compressed = compress_with_header(&data, Size::Byte).unwrap();
+    let truncated = &compressed[..compressed.len() - 3];
+    assert!(CmpCursor::new(truncated).is_err());
+    assert!(CmpCursor::redecoding(truncated).is_err());
+}
@@ -0,0 +1,48 @@
+//! Round-trips every file in `tests/fixtures` through each mode its length
+//! allows, so adding a fixture automatically adds coverage.
+
+use std::fs;
+use std::path::PathBuf;
+
+use sega_cmp::{compress, compress_with_header, decompress, decompress_with_header, Size};
+
+const SIZES: [(Size, usize); 3] = [(Size::Byte, 1), (Size::Word, 2), (Size::Longword, 4)];
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn every_fixture_round_trips_in_every_aligned_mode() {
+    for path in fixtures() {
+        let data = fs::read(&path).unwrap();
+        for &(size, unit_len) in SIZES.iter() {
+            if data.len() % unit_len != 0 {
+                continue;
+            }
+            let compressed = compress(&data, size)
+                .unwrap_or_else(|e| panic!("{:?} ({:?}) didn't compress: {}", path, size, e));
+            assert!(
+                decompress(&compressed, size).unwrap() == data,
+                "{:?} ({:?}) didn't round-trip",
+                path,
+                size
+            );
+
+            let blob = compress_with_header(&data, size).unwrap();
+            assert!(
+                decompress_with_header(&blob).unwrap() == data,
+                "{:?} ({:?}) didn't round-trip with a header",
+                path,
+                size
+            );
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks the compressed output for every file in `tests/fixtures` against the
+//! hashes recorded in `tests/hashes.toml`, so that any change to the encoder's
+//! output is caught without storing every compressed file in the repository.
+//!
+//! When output changes deliberately, rerun with `SEGA_CMP_BLESS=1` set to
+//! rewrite `tests/hashes.toml`, and review the changed hashes.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use sega_cmp::{compress, Size};
+
+const SIZES: [(Size, &str); 3] = [
+    (Size::Byte, "byte"),
+    (Size::Word, "word"),
+    (Size::Longword, "longword"),
+];
+
+fn manifest_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+/// 64-bit FNV-1a, which is plenty to notice a change in output.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The hash of each fixture's output in each mode its length allows, keyed
+/// by file name and then mode.
+fn compute_hashes() -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for entry in fs::read_dir(manifest_path("tests/fixtures")).unwrap() {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let modes: BTreeMap<String, String> = SIZES
+            .iter()
+            .filter_map(|&(size, mode)| {
+                let compressed = compress(&data, size).ok()?;
+                Some((mode.to_string(), format!("{:016x}", fnv1a(&compressed))))
+            })
+            .collect();
+        hashes.insert(name, modes);
+    }
+    hashes
+}
+
+/// Reads the subset of TOML `tests/hashes.toml` is written in: a table per
+/// fixture holding a string per mode.
+fn parse_hashes(text: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    let mut table = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("[\"").and_then(|l| l.strip_suffix("\"]")) {
+            table = Some(name.to_string());
+            hashes.insert(name.to_string(), BTreeMap::new());
+            continue;
+        }
+        let (key, value) = line.split_once('=').expect("expected `mode = \"hash\"`");
+        let table = table.as_ref().expect("hash outside of a fixture's table");
+        hashes.get_mut(table).unwrap().insert(
+            key.trim().to_string(),
+            value.trim().trim_matches('"').to_string(),
+        );
+    }
+    hashes
+}
+
+fn write_hashes(hashes: &BTreeMap<String, BTreeMap<String, String>>) -> String {
+    let mut text = String::from(
+        "# Hashes of the compressed output of each file in tests/fixtures, checked by\n\
+         # tests/hashes.rs. Regenerate with `SEGA_CMP_BLESS=1 cargo test --test hashes`.\n",
+    );
+    for (name, modes) in hashes {
+        text.push_str(&format!("\n[\"{}\"]\n", name));
+        for (mode, hash) in modes {
+            text.push_str(&format!("{} = \"{}\"\n", mode, hash));
+        }
+    }
+    text
+}
+
+#[test]
+fn fixture_output_matches_recorded_hashes() {
+    let path = manifest_path("tests/hashes.toml");
+    let actual = compute_hashes();
+    if env::var_os("SEGA_CMP_BLESS").is_some() {
+        fs::write(&path, write_hashes(&actual)).unwrap();
+        return;
+    }
+
+    let expected = parse_hashes(&fs::read_to_string(&path).unwrap());
+    for (name, modes) in &actual {
+        for (mode, hash) in modes {
+            let recorded = expected.get(name).and_then(|modes| modes.get(mode));
+            assert_eq!(
+                recorded,
+                Some(hash),
+                "{} ({}) compressed to different output than recorded",
+                name,
+                mode
+            );
+        }
+    }
+    assert_eq!(
+        expected, actual,
+        "tests/hashes.toml has hashes for output which wasn't produced"
+    );
+}
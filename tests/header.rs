@@ -0,0 +1,160 @@
+use sega_cmp::{
+    compress_with_header, compress_with_signature, create_header, create_header_with_endianness,
+    decompress_with_header, header_len_for, max_size_for_width, parse_header, strip_signature,
+    CompressionError, Endianness, HeaderBuilder, HeaderWidth, Size,
+};
+
+const SIZES: [(Size, u8); 3] = [(Size::Byte, 0x0), (Size::Word, 0x4), (Size::Longword, 0xC)];
+
+#[test]
+fn header_at_16_bit_limit_stays_16_bit() {
+    for &(size, size_byte) in SIZES.iter() {
+        assert_eq!(create_header(65535, size), vec![0, size_byte, 0xFF, 0xFF]);
+    }
+}
+
+#[test]
+fn header_above_16_bit_limit_is_32_bit() {
+    for &(size, size_byte) in SIZES.iter() {
+        assert_eq!(
+            create_header(65536, size),
+            vec![0, size_byte, 0, 0, 0x00, 0x01, 0x00, 0x00]
+        );
+    }
+}
+
+#[test]
+fn header_32_bit_size_is_big_endian() {
+    for &(size, size_byte) in SIZES.iter() {
+        assert_eq!(
+            create_header(0x0123_4567, size),
+            vec![0, size_byte, 0, 0, 0x01, 0x23, 0x45, 0x67]
+        );
+    }
+}
+
+#[test]
+fn header_endianness_only_affects_the_size_field() {
+    for &(size, size_byte) in SIZES.iter() {
+        assert_eq!(
+            create_header_with_endianness(0x1234, size, Endianness::Big),
+            vec![0, size_byte, 0x12, 0x34]
+        );
+        assert_eq!(
+            create_header_with_endianness(0x1234, size, Endianness::Little),
+            vec![0, size_byte, 0x34, 0x12]
+        );
+        assert_eq!(
+            create_header_with_endianness(0x0123_4567, size, Endianness::Big),
+            vec![0, size_byte, 0, 0, 0x01, 0x23, 0x45, 0x67]
+        );
+        assert_eq!(
+            create_header_with_endianness(0x0123_4567, size, Endianness::Little),
+            vec![0, size_byte, 0, 0, 0x67, 0x45, 0x23, 0x01]
+        );
+    }
+}
+
+#[test]
+fn header_32_bit_near_i32_max() {
+    for &(size, size_byte) in SIZES.iter() {
+        assert_eq!(
+            create_header(i32::MAX - 1, size),
+            vec![0, size_byte, 0, 0, 0x7F, 0xFF, 0xFF, 0xFE]
+        );
+        assert_eq!(
+            create_header(i32::MAX, size),
+            vec![0, size_byte, 0, 0, 0x7F, 0xFF, 0xFF, 0xFF]
+        );
+    }
+}
+
+#[test]
+fn header_len_for_matches_create_header() {
+    let boundary = 65530..=65541;
+    let sweep = (0..=16)
+        .chain(boundary)
+        .chain((17..31).map(|bit| 1 << bit))
+        .chain([i32::MAX as u32 - 1, i32::MAX as u32]);
+    for len in sweep {
+        let expected = if len > 65535 { 8 } else { 4 };
+        assert_eq!(header_len_for(len), expected);
+        for &(size, _) in SIZES.iter() {
+            assert_eq!(create_header(len as i32, size).len(), expected);
+        }
+    }
+}
+
+#[test]
+fn signatures_are_only_stripped_when_asked_for() {
+    let data = [0x41; 32];
+    let blob = compress_with_signature(&data, Size::Byte, b"CMP1").unwrap();
+    assert!(blob.starts_with(b"CMP1"));
+    let stripped = strip_signature(&blob, b"CMP1").unwrap();
+    assert_eq!(
+        stripped,
+        &compress_with_header(&data, Size::Byte).unwrap()[..]
+    );
+    assert_eq!(parse_header(stripped).unwrap().decompressed_size, 32);
+    assert_eq!(decompress_with_header(stripped).unwrap(), data.to_vec());
+
+    assert!(matches!(
+        parse_header(&blob),
+        Err(CompressionError::InvalidHeader)
+    ));
+    assert!(matches!(
+        strip_signature(stripped, b"CMP1"),
+        Err(CompressionError::SignatureMismatch)
+    ));
+}
+
+#[test]
+fn max_size_for_width_matches_the_header_switch() {
+    assert_eq!(max_size_for_width(HeaderWidth::Bits16), 65535);
+    assert_eq!(max_size_for_width(HeaderWidth::Bits32), u32::MAX);
+    let limit = max_size_for_width(HeaderWidth::Bits16);
+    assert_eq!(header_len_for(limit), 4);
+    assert_eq!(header_len_for(limit + 1), 8);
+}
+
+#[test]
+fn header_builder_matches_create_header() {
+    for &(size, _) in SIZES.iter() {
+        for &len in [0, 65535, 65536, i32::MAX as u32].iter() {
+            let built = HeaderBuilder::new(size)
+                .decompressed_size(len)
+                .build()
+                .unwrap();
+            assert_eq!(built, create_header(len as i32, size));
+        }
+    }
+
+    let header = HeaderBuilder::new(Size::Longword)
+        .decompressed_size(u32::MAX)
+        .width(HeaderWidth::Bits32)
+        .endianness(Endianness::Little)
+        .signature(b"CMP!")
+        .build()
+        .unwrap();
+    assert_eq!(header, b"CMP!\x00\x0C\x00\x00\xFF\xFF\xFF\xFF".to_vec());
+}
+
+#[test]
+fn header_builder_rejects_widths_which_cant_hold_the_size() {
+    for &(len, width) in [(65536, HeaderWidth::Bits16), (65535, HeaderWidth::Bits32)].iter() {
+        let result = HeaderBuilder::new(Size::Byte)
+            .decompressed_size(len)
+            .width(width)
+            .build();
+        assert!(matches!(
+            result,
+            Err(CompressionError::UnsupportedHeaderWidth { decompressed_size, width: w })
+                if decompressed_size == len && w == width
+        ));
+    }
+    assert!(HeaderBuilder::new(Size::Byte)
+        .decompressed_size(65535)
+        .width(HeaderWidth::Bits16)
+        .build()
+        .is_ok());
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "opcodes")]
+
+use sega_cmp::opcodes::{
+    LITERAL_FLAG_16, LITERAL_FLAG_8, MAX_RUN_COUNT_8, MIN_LITERAL_COUNT_8, RUN_LENGTH_BIAS,
+};
+use sega_cmp::{compress, Size};
+
+#[test]
+fn opcodes_match_the_encoder_output() {
+    // A run of 8 A, then a literal of 16 distinct bytes
+    let mut data = vec![b'A'; 8];
+    data.extend(0..16);
+    data.extend_from_slice(&[0; 16]);
+    let compressed = compress(&data, Size::Byte).unwrap();
+    assert_eq!(compressed[0] as usize + RUN_LENGTH_BIAS, 8);
+    assert_eq!(compressed[0] & LITERAL_FLAG_8, 0);
+    assert_ne!(compressed[2] & LITERAL_FLAG_8, 0);
+    assert_eq!(-(compressed[2] as i8), 16);
+
+    // The longest run fills a count, and the longest literal empties one
+    let compressed = compress(&[0; 200], Size::Byte).unwrap();
+    assert_eq!(compressed[0] as i8, MAX_RUN_COUNT_8);
+    let mut data: Vec<u8> = (0..=255).cycle().take(128).collect();
+    data.extend_from_slice(&[0; 256]);
+    assert_eq!(
+        compress(&data, Size::Byte).unwrap()[0] as i8,
+        MIN_LITERAL_COUNT_8
+    );
+
+    let compressed = compress(&[1, 2, 3, 4, 0, 0, 0, 0, 0, 0], Size::Word).unwrap();
+    assert_ne!(
+        u16::from_be_bytes([compressed[0], compressed[1]]) & LITERAL_FLAG_16,
+        0
+    );
+}
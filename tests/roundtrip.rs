@@ -0,0 +1,151 @@
+use proptest::prelude::*;
+
+use sega_cmp::{
+    compress, compress_all_modes, compress_with_options, decompress, decompress_with_options,
+    exact_sizes, max_compressed_len, optimize, CompressOptions, CompressionError, Effort, Size,
+};
+
+const SIZES: [Size; 3] = [Size::Byte, Size::Word, Size::Longword];
+
+/// Data made of runs of a single byte, each a whole number of longwords and at
+/// least three longwords long, so it shrinks in every mode.
+fn runs() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec((any::<u8>(), 3usize..64), 1..24).prop_map(|runs| {
+        runs.into_iter()
+            .flat_map(|(value, longwords)| std::iter::repeat_n(value, longwords * 4))
+            .collect()
+    })
+}
+
+/// Arbitrary data whose length is a multiple of 4.
+fn aligned_bytes() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<[u8; 4]>(), 0..256)
+        .prop_map(|units| units.into_iter().flatten().collect())
+}
+
+/// Short runs, including runs of a single byte, truncated to a multiple of 4
+/// bytes; a mix of runs and literals where the choice between them matters.
+fn short_runs() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec((0u8..4, 1usize..6), 1..128).prop_map(|runs| {
+        let mut data: Vec<u8> = runs
+            .into_iter()
+            .flat_map(|(value, len)| std::iter::repeat_n(value, len))
+            .collect();
+        data.truncate(data.len() / 4 * 4);
+        data
+    })
+}
+
+proptest! {
+    #[test]
+    fn runs_round_trip_in_every_mode(data in runs()) {
+        for &size in SIZES.iter() {
+            let compressed = compress(&data, size).unwrap();
+            prop_assert_eq!(decompress(&compressed, size).unwrap(), data.clone());
+        }
+    }
+
+    #[test]
+    fn optimized_payloads_round_trip_and_never_grow(data in runs()) {
+        for &size in SIZES.iter() {
+            let compressed = compress(&data, size).unwrap();
+            let optimized = optimize(&compressed, size).unwrap();
+            prop_assert!(optimized.len() <= compressed.len());
+            prop_assert_eq!(decompress(&optimized, size).unwrap(), data.clone());
+        }
+    }
+
+    #[test]
+    fn aligned_data_round_trips_when_it_compresses(data in aligned_bytes()) {
+        for &size in SIZES.iter() {
+            match compress(&data, size) {
+                Ok(compressed) => prop_assert_eq!(decompress(&compressed, size).unwrap(), data.clone()),
+                // The C encoder refuses to expand incompressible data
+                Err(CompressionError::EncoderFailed) => {}
+                Err(e) => return Err(TestCaseError::fail(format!("{:?}: {}", size, e))),
+            }
+        }
+    }
+
+    #[test]
+    fn best_effort_round_trips_in_every_mode(data in aligned_bytes()) {
+        let options = CompressOptions {
+            effort: Effort::Best,
+            ..CompressOptions::default()
+        };
+        for &size in SIZES.iter() {
+            let compressed = compress_with_options(&data, size, options).unwrap();
+            prop_assert!(compressed.len() <= max_compressed_len(data.len(), size));
+            prop_assert_eq!(decompress(&compressed, size).unwrap(), data.clone());
+        }
+    }
+
+    #[test]
+    fn best_effort_is_never_larger_than_fast(data in short_runs()) {
+        let options = CompressOptions {
+            effort: Effort::Best,
+            ..CompressOptions::default()
+        };
+        for &size in SIZES.iter() {
+            let best = compress_with_options(&data, size, options).unwrap();
+            prop_assert_eq!(decompress(&best, size).unwrap(), data.clone());
+            match compress(&data, size) {
+                Ok(fast) => prop_assert!(best.len() <= fast.len()),
+                Err(CompressionError::EncoderFailed) => {}
+                Err(e) => return Err(TestCaseError::fail(format!("{:?}: {}", size, e))),
+            }
+        }
+    }
+
+    #[test]
+    fn extended_runs_round_trip_in_every_mode(data in runs(), best in any::<bool>()) {
+        let options = CompressOptions {
+            effort: if best { Effort::Best } else { Effort::Fast },
+            extended_runs: true,
+            ..CompressOptions::default()
+        };
+        for &size in SIZES.iter() {
+            let compressed = compress_with_options(&data, size, options).unwrap();
+            prop_assert_eq!(
+                decompress_with_options(&compressed, size, options).unwrap(),
+                data.clone()
+            );
+        }
+    }
+
+    #[test]
+    fn word_aligned_data_is_rejected_only_by_longword(mut data in runs(), value in any::<u8>()) {
+        data.extend_from_slice(&[value, value]);
+        prop_assert_eq!(data.len() % 4, 2);
+
+        for &size in [Size::Byte, Size::Word].iter() {
+            let compressed = compress(&data, size).unwrap();
+            prop_assert_eq!(decompress(&compressed, size).unwrap(), data.clone());
+        }
+        let misaligned = matches!(
+            compress(&data, Size::Longword),
+            Err(CompressionError::Misaligned { width: 4, remainder: 2 })
+        );
+        prop_assert!(misaligned);
+    }
+
+    #[test]
+    fn all_modes_match_individual_calls(data in short_runs(), extra in 0usize..4) {
+        // Unaligned lengths too, so some modes are misaligned
+        let data = &data[..data.len().saturating_sub(extra)];
+        let modes = compress_all_modes(data).unwrap();
+        for (mode, &size) in modes.iter().zip(SIZES.iter()) {
+            let expected = compress(data, size).ok();
+            prop_assert_eq!(mode.compressed.as_ref().ok(), expected.as_ref());
+        }
+    }
+
+    #[test]
+    fn exact_sizes_match_compressed_lengths(data in short_runs(), extra in 0usize..4) {
+        let data = &data[..data.len().saturating_sub(extra)];
+        let sizes = exact_sizes(data).unwrap();
+        for (&len, &size) in sizes.iter().zip(SIZES.iter()) {
+            prop_assert_eq!(len, compress(data, size).ok().map(|c| c.len()));
+        }
+    }
+}
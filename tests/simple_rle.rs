@@ -0,0 +1,24 @@
+use sega_cmp::simple_rle::{decode, encode};
+
+#[test]
+fn documented_example_round_trips() {
+    assert_eq!(encode(b"AAAAAAAABBBBAABA"), b"8A4B2A1B1A".to_vec());
+    assert_eq!(decode(b"8A4B2A1B1A").unwrap(), b"AAAAAAAABBBBAABA".to_vec());
+}
+
+#[test]
+fn long_runs_and_digits_round_trip() {
+    let mut data = vec![b'1'; 20];
+    data.extend_from_slice(b"99x");
+    let encoded = encode(&data);
+    assert_eq!(encoded, b"919121291x".to_vec());
+    assert_eq!(decode(&encoded).unwrap(), data);
+    assert_eq!(encode(b""), Vec::<u8>::new());
+}
+
+#[test]
+fn malformed_input_is_rejected() {
+    assert_eq!(decode(b"8A4"), None);
+    assert_eq!(decode(b"0A"), None);
+    assert_eq!(decode(b"AA"), None);
+}
@@ -0,0 +1,127 @@
+use sega_cmp::{
+    command_histogram, compress, compress_if_worthwhile, compress_with_stats, estimate_decode_cost,
+    report, Size,
+};
+
+fn report_files() -> Vec<(String, Vec<u8>)> {
+    vec![
+        ("literal".to_string(), b"ABCD".to_vec()),
+        ("half".to_string(), [&[0x5A; 8][..], b"ABCDEFGH"].concat()),
+        ("runs".to_string(), vec![0x41; 64]),
+    ]
+}
+
+#[test]
+fn poorly_compressible_data_is_warned_about() {
+    let mut data = vec![0x41; 8];
+    data.extend((0..64).map(|i| i as u8));
+    let (_, stats) = compress_with_stats(&data, Size::Byte).unwrap();
+    assert!(stats.compressed_len < stats.original_len);
+    assert_eq!(stats.warnings.len(), 1);
+
+    let (_, stats) = compress_with_stats(&[0x41; 64], Size::Byte).unwrap();
+    assert!(stats.warnings.is_empty());
+}
+
+#[test]
+fn decode_cost_counts_commands_and_units() {
+    let compressed = compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap();
+    let cost = estimate_decode_cost(&compressed, Size::Byte).unwrap();
+    assert_eq!(cost.commands, 4);
+    assert_eq!(cost.units, 16);
+
+    // The same data as one long literal costs more to decode
+    let literal = [&[0xF0][..], b"AAAAAAAABBBBAABA"].concat();
+    assert!(estimate_decode_cost(&literal, Size::Byte).unwrap().cycles > cost.cycles);
+}
+
+#[test]
+fn compress_if_worthwhile_rejects_small_savings() {
+    let mut data = vec![0x41; 8];
+    data.extend((0..64).map(|i| i as u8));
+    let compressed = compress(&data, Size::Byte).unwrap();
+    assert!(compressed.len() < data.len());
+    assert_eq!(
+        compress_if_worthwhile(&data, Size::Byte, 0.5).unwrap(),
+        None
+    );
+    assert_eq!(
+        compress_if_worthwhile(&data, Size::Byte, 0.0).unwrap(),
+        Some(compressed)
+    );
+
+    assert!(compress_if_worthwhile(&[0x41; 64], Size::Byte, 0.5)
+        .unwrap()
+        .is_some());
+    // Incompressible data isn't an error
+    assert_eq!(
+        compress_if_worthwhile(b"ABCD", Size::Byte, 0.1).unwrap(),
+        None
+    );
+    assert!(compress_if_worthwhile(&[0; 3], Size::Word, 0.1).is_err());
+}
+
+#[test]
+fn histogram_buckets_commands_by_length() {
+    // Runs of 8, 4 and 2, a literal of 1, and a run of 200 split into 129 and 71
+    let mut data = b"AAAAAAAABBBBAAB".to_vec();
+    data.extend_from_slice(&[0; 200]);
+    let compressed = compress(&data, Size::Byte).unwrap();
+    let histogram = command_histogram(&compressed, Size::Byte).unwrap();
+    assert_eq!(histogram.runs, vec![0, 1, 1, 1, 0, 0, 1, 1]);
+    assert_eq!(histogram.literals, vec![1]);
+
+    let empty = command_histogram(&[], Size::Byte).unwrap();
+    assert!(empty.runs.is_empty() && empty.literals.is_empty());
+    assert!(command_histogram(&[0x00], Size::Byte).is_err());
+}
+
+#[test]
+fn report_names_each_file_in_order_of_savings() {
+    let names: Vec<String> = report(&report_files(), Size::Byte)
+        .into_iter()
+        .map(|report| report.name)
+        .collect();
+    assert_eq!(names, vec!["runs", "half", "literal"]);
+}
+
+#[test]
+fn report_gives_original_lengths() {
+    let lens: Vec<usize> = report(&report_files(), Size::Byte)
+        .iter()
+        .map(|report| report.original_len)
+        .collect();
+    assert_eq!(lens, vec![64, 16, 4]);
+}
+
+#[test]
+fn report_gives_compressed_lengths() {
+    let lens: Vec<Option<usize>> = report(&report_files(), Size::Byte)
+        .iter()
+        .map(|report| report.compressed_len)
+        .collect();
+    // A run of 64; a run of 8 then a literal of 8; and "ABCD", which would grow
+    assert_eq!(lens, vec![Some(2), Some(11), None]);
+
+    // Files which aren't a whole number of units can't be compressed either
+    let reports = report(&[("odd".to_string(), vec![0; 5])], Size::Word);
+    assert_eq!(reports[0].compressed_len, None);
+}
+
+#[test]
+fn report_gives_ratios() {
+    let ratios: Vec<Option<f64>> = report(&report_files(), Size::Byte)
+        .iter()
+        .map(|report| report.ratio)
+        .collect();
+    assert_eq!(ratios, vec![Some(2.0 / 64.0), Some(11.0 / 16.0), None]);
+}
+
+#[test]
+fn report_savings_are_zero_for_files_which_did_not_compress() {
+    let savings: Vec<usize> = report(&report_files(), Size::Byte)
+        .iter()
+        .map(|report| report.savings())
+        .collect();
+    assert_eq!(savings, vec![62, 5, 0]);
+}
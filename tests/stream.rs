@@ -0,0 +1,136 @@
+use std::io;
+use std::io::{Read, Write};
+
+use sega_cmp::{compress_with_header, decompress_with_header, CmpDecoder, CmpEncoder, Size};
+
+fn sample() -> Vec<u8> {
+    let mut data = vec![0x41; 64];
+    data.extend((0..64).map(|i| i as u8));
+    data
+}
+
+#[test]
+fn encoder_output_round_trips_through_the_decoder() {
+    let data = sample();
+    let mut encoder = CmpEncoder::new(Vec::new(), Size::Word);
+    encoder.write_all(&data[..10]).unwrap();
+    encoder.write_all(&data[10..]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(compressed, compress_with_header(&data, Size::Word).unwrap());
+
+    let mut decoded = Vec::new();
+    CmpDecoder::new(&compressed[..])
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, data);
+}
+
+#[test]
+fn finishing_twice_writes_the_stream_once() {
+    let data = sample();
+    let mut encoder = CmpEncoder::new(Vec::new(), Size::Byte);
+    encoder.write_all(&data).unwrap();
+    encoder.try_finish().unwrap();
+    encoder.try_finish().unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(compressed, compress_with_header(&data, Size::Byte).unwrap());
+}
+
+#[test]
+fn dropping_the_encoder_finishes_the_stream() {
+    let data = sample();
+    let mut out = Vec::new();
+    {
+        let mut encoder = CmpEncoder::new(&mut out, Size::Byte);
+        encoder.write_all(&data).unwrap();
+    }
+    assert_eq!(decompress_with_header(&out).unwrap(), data);
+
+    // After an explicit finish, dropping writes nothing more
+    let mut out = Vec::new();
+    {
+        let mut encoder = CmpEncoder::new(&mut out, Size::Byte);
+        encoder.write_all(&data).unwrap();
+        encoder.try_finish().unwrap();
+    }
+    assert_eq!(out, compress_with_header(&data, Size::Byte).unwrap());
+}
+
+/// Accepts a limited number of bytes, failing once they've been used up.
+struct FlakyWriter {
+    out: Vec<u8>,
+    allowance: usize,
+}
+
+impl Write for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.allowance == 0 {
+            return Err(io::Error::other("out of space"));
+        }
+        let n = buf.len().min(self.allowance);
+        self.out.extend_from_slice(&buf[..n]);
+        self.allowance -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn a_failed_finish_can_be_retried() {
+    let data = sample();
+    let mut encoder = CmpEncoder::new(
+        FlakyWriter {
+            out: Vec::new(),
+            allowance: 6,
+        },
+        Size::Byte,
+    );
+    encoder.write_all(&data).unwrap();
+    assert!(encoder.try_finish().is_err());
+
+    encoder.get_mut().allowance = usize::MAX;
+    let writer = encoder.finish().unwrap();
+    assert_eq!(writer.out, compress_with_header(&data, Size::Byte).unwrap());
+}
+
+#[test]
+fn decoder_leaves_data_after_the_stream_unread() {
+    let data = sample();
+    let mut input = compress_with_header(&data, Size::Longword).unwrap();
+    input.extend_from_slice(b"trailer");
+
+    let mut decoder = CmpDecoder::new(&input[..]);
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, data);
+
+    let mut rest = Vec::new();
+    decoder.into_inner().read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"trailer");
+}
+
+#[test]
+fn decoder_reports_a_truncated_stream() {
+    let input = compress_with_header(&sample(), Size::Byte).unwrap();
+    let mut decoded = Vec::new();
+    let error = CmpDecoder::new(&input[..input.len() - 1])
+        .read_to_end(&mut decoded)
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn decoder_reads_an_empty_stream_followed_by_a_word_stream() {
+    let mut input = compress_with_header(b"", Size::Byte).unwrap();
+    assert_eq!(input, [0, 0, 0, 0]);
+    input.extend_from_slice(&[0x00, 0x04, 0x00, 0x20, 0x00, 0x0e, 0x00, 0x07]);
+
+    let mut decoded = Vec::new();
+    CmpDecoder::new(&input[..])
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert!(decoded.is_empty());
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "text")]
+
+use sega_cmp::text::{compress_text, decompress_text};
+use sega_cmp::{compress_with_header, CompressionError, Size};
+
+fn is_control(byte: u8) -> bool {
+    byte < 0x20
+}
+
+#[test]
+fn control_codes_are_restored_in_place() {
+    let text = b"\x01AAAAAAAA\nAAAAAAAA\x02\x03AAAAAAAA\n";
+    let compressed = compress_text(text, is_control).unwrap();
+    assert_eq!(decompress_text(&compressed).unwrap(), text.to_vec());
+    // 24 A in one run, instead of three runs split by the codes
+    assert!(compressed.ends_with(&[0x00, 0x00, 0x00, 0x18, 0x16, b'A']));
+}
+
+#[test]
+fn text_without_control_codes_has_an_empty_map() {
+    let text = [b'A'; 32];
+    let compressed = compress_text(&text, is_control).unwrap();
+    let mut expected = vec![0, 0, 0, 0];
+    expected.extend(compress_with_header(&text, Size::Byte).unwrap());
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+fn offsets_past_the_text_are_rejected() {
+    let mut compressed = vec![0, 0, 0, 1, 0, 0, 0, 40, b'\n'];
+    compressed.extend(compress_with_header(&[b'A'; 32], Size::Byte).unwrap());
+    assert!(matches!(
+        decompress_text(&compressed),
+        Err(CompressionError::InvalidHeader)
+    ));
+    assert!(matches!(
+        decompress_text(&compressed[..6]),
+        Err(CompressionError::Truncated)
+    ));
+}
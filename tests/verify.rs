@@ -0,0 +1,255 @@
+use sega_cmp::{
+    canonicalize, check_decoder_limits, check_payload_span, compress, compress_with_header,
+    decompress, decompress_with_header, diagnose, diff_report, first_difference, is_canonical,
+    is_deterministic, is_position_independent, looks_like_cmp, optimize, repair_header,
+    CompressionError, DecodedCommand, Size,
+};
+
+#[test]
+fn diagnose_reports_where_a_short_stream_ends() {
+    // Header declares 16 bytes; the payload is a run of 4
+    let diagnostics = diagnose(&[0x00, 0x00, 0x00, 0x10, 0x02, 0x41]);
+    assert_eq!(diagnostics.declared_size, Some(16));
+    assert_eq!(diagnostics.actual_size, 4);
+    assert_eq!(diagnostics.commands, 1);
+    assert_eq!(diagnostics.mismatch_offset, Some(6));
+    assert!(matches!(
+        diagnostics.error,
+        Some(CompressionError::SizeMismatch {
+            expected: 16,
+            actual: 4
+        })
+    ));
+}
+
+#[test]
+fn diagnose_reports_the_failing_command() {
+    // A run of 4, then a literal claiming 5 bytes with only 1 following
+    let diagnostics = diagnose(&[0x00, 0x00, 0x00, 0x10, 0x02, 0x41, 0xFB, 0x42]);
+    assert_eq!(diagnostics.commands, 1);
+    assert_eq!(diagnostics.mismatch_offset, Some(6));
+    assert!(matches!(
+        diagnostics.error,
+        Some(CompressionError::Truncated)
+    ));
+}
+
+#[test]
+fn diagnose_accepts_a_consistent_stream() {
+    let diagnostics = diagnose(&[0x00, 0x00, 0x00, 0x04, 0x02, 0x41]);
+    assert!(diagnostics.is_ok());
+    assert_eq!(diagnostics.mismatch_offset, None);
+}
+
+#[test]
+fn compressed_streams_look_like_cmp() {
+    let data = [[0x11u8; 16], [0x22; 16]].concat();
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        assert!(looks_like_cmp(&compress_with_header(&data, size).unwrap()));
+    }
+}
+
+#[test]
+fn plain_data_does_not_look_like_cmp() {
+    assert!(!looks_like_cmp(b"Hello, world!"));
+    assert!(!looks_like_cmp(&[0x00; 64]));
+    // A valid stream followed by trailing data
+    assert!(!looks_like_cmp(&[0x00, 0x00, 0x00, 0x04, 0x02, 0x41, 0x00]));
+}
+
+#[test]
+fn stock_payloads_are_canonical() {
+    let compressed = compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap();
+    assert!(is_canonical(&compressed, Size::Byte).unwrap());
+}
+
+#[test]
+fn payloads_from_other_encoders_are_not_canonical() {
+    // "AAAA" stored as a literal rather than a run
+    assert!(!is_canonical(&[0xFC, 0x41, 0x41, 0x41, 0x41], Size::Byte).unwrap());
+    // The stock encoder can't compress "ABCD" at all
+    assert!(!is_canonical(&[0xFC, 0x41, 0x42, 0x43, 0x44], Size::Byte).unwrap());
+    assert!(matches!(
+        is_canonical(&[0xFC, 0x41], Size::Byte),
+        Err(CompressionError::Truncated)
+    ));
+}
+
+#[test]
+fn canonicalize_flags_streams_from_other_encoders() {
+    // "AAAA" stored as a literal rather than a run
+    let literal = [0x00, 0x00, 0x00, 0x04, 0xFC, 0x41, 0x41, 0x41, 0x41];
+    let (canonical, differed) = canonicalize(&literal).unwrap();
+    assert_eq!(canonical, vec![0x00, 0x00, 0x00, 0x04, 0x02, 0x41]);
+    assert!(differed);
+    assert_eq!(canonicalize(&canonical).unwrap(), (canonical, false));
+}
+
+#[test]
+fn canonicalize_leaves_incompressible_streams_alone() {
+    // "ABCD" as a literal, which the stock encoder can't compress
+    let literal = [0x00, 0x00, 0x00, 0x04, 0xFC, 0x41, 0x42, 0x43, 0x44];
+    assert_eq!(canonicalize(&literal).unwrap(), (literal.to_vec(), false));
+}
+
+#[test]
+fn diagnose_notes_an_overshoot_without_failing() {
+    // Header declares 3 bytes; the payload is a run of 4
+    let diagnostics = diagnose(&[0x00, 0x00, 0x00, 0x03, 0x02, 0x41]);
+    assert!(diagnostics.is_ok());
+    assert_eq!(diagnostics.actual_size, 4);
+    assert_eq!(diagnostics.mismatch_offset, Some(4));
+}
+
+#[test]
+fn repair_header_restores_the_declared_size() {
+    let data = [0x41; 300];
+    let blob = compress_with_header(&data, Size::Byte).unwrap();
+    let mut corrupt = blob.clone();
+    corrupt[3] ^= 0x55;
+    assert_eq!(repair_header(&corrupt).unwrap(), blob);
+
+    // A literal claiming more units than follow
+    assert!(matches!(
+        repair_header(&[0x00, 0x00, 0x00, 0x04, 0xFB, 0x41]),
+        Err(CompressionError::Truncated)
+    ));
+}
+
+#[test]
+fn check_payload_span_rejects_impossible_lengths() {
+    let data = [0x41; 300];
+    let blob = compress_with_header(&data, Size::Byte).unwrap();
+    assert!(check_payload_span(&blob, blob.len() - 4).is_ok());
+    // 300 bytes needs at least three runs of 2 bytes each
+    assert!(matches!(
+        check_payload_span(&blob, 4),
+        Err(CompressionError::ImplausiblePayloadLen {
+            len: 4,
+            min: 6,
+            max: 600
+        })
+    ));
+    assert!(check_payload_span(&blob, 601).is_err());
+}
+
+#[test]
+fn check_payload_span_accepts_a_literal_per_unit() {
+    let blob = [0x00, 0x00, 0x00, 0x02, 0xff, 0x41, 0xff, 0x42];
+    assert_eq!(decompress_with_header(&blob).unwrap(), b"AB");
+    assert!(check_payload_span(&blob, 4).is_ok());
+}
+
+#[test]
+fn payloads_decode_the_same_wherever_they_are() {
+    let data: Vec<u8> = (0..256u32)
+        .flat_map(|i| ((i / 8) as u16).to_be_bytes())
+        .collect();
+    let compressed = compress(&data, Size::Word).unwrap();
+    assert!(is_position_independent(&compressed, Size::Word));
+
+    // Moving the payload to another (aligned) offset in a larger buffer
+    // doesn't change what it decodes to
+    let mut relocated = vec![0xFF; 6];
+    relocated.extend_from_slice(&compressed);
+    relocated.extend_from_slice(&[0xFF; 2]);
+    let moved = &relocated[6..6 + compressed.len()];
+    assert_eq!(decompress(moved, Size::Word).unwrap(), data);
+
+    assert!(is_position_independent(&[], Size::Word));
+    assert!(!is_position_independent(
+        &compressed[..compressed.len() - 1],
+        Size::Word
+    ));
+}
+
+#[test]
+fn decoder_limits_cap_the_number_of_commands() {
+    let compressed = compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap();
+    assert!(check_decoder_limits(&compressed, Size::Byte, 4).is_ok());
+    assert!(matches!(
+        check_decoder_limits(&compressed, Size::Byte, 3),
+        Err(CompressionError::TooManyCommands {
+            commands: 4,
+            limit: 3
+        })
+    ));
+    assert!(check_decoder_limits(&[], Size::Byte, 0).is_ok());
+    assert!(matches!(
+        check_decoder_limits(&[0x00], Size::Byte, 10),
+        Err(CompressionError::Truncated)
+    ));
+}
+
+#[test]
+fn compression_is_deterministic() {
+    // Runs broken up by literals, to exercise both kinds of command
+    let data: Vec<u8> = (0..4096u32)
+        .map(|i| if i % 64 < 40 { 0 } else { i as u8 })
+        .collect();
+    for &size in [Size::Byte, Size::Word, Size::Longword].iter() {
+        assert!(is_deterministic(&data, size, 16).unwrap());
+    }
+    assert!(is_deterministic(&data, Size::Byte, 0).unwrap());
+    assert!(is_deterministic(&data[..3], Size::Word, 2).is_err());
+}
+
+#[test]
+fn first_difference_finds_the_first_differing_byte() {
+    assert_eq!(first_difference(b"abc", b"abc"), None);
+    assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+    assert_eq!(first_difference(b"ab", b"abc"), Some(2));
+    assert_eq!(first_difference(b"", b""), None);
+}
+
+#[test]
+fn diff_report_decodes_the_diverging_commands() {
+    let a = compress(b"AAAAAAAABBBBAABA", Size::Byte).unwrap();
+    let b = compress(b"AAAAAAAABBBBBBBA", Size::Byte).unwrap();
+    let report = diff_report(&a, &b, Size::Byte).unwrap();
+    assert_eq!(report.offset, 2);
+    assert_eq!(report.command_index, 1);
+    assert_eq!(report.command_offset, 2);
+    assert_eq!(
+        report.a,
+        Some(DecodedCommand::Run {
+            unit: vec![b'B'],
+            count: 4
+        })
+    );
+    assert_eq!(
+        report.b,
+        Some(DecodedCommand::Run {
+            unit: vec![b'B'],
+            count: 7
+        })
+    );
+
+    // A payload which stops short has no command where the other continues
+    let report = diff_report(&a[..4], &a, Size::Byte).unwrap();
+    assert_eq!((report.offset, report.command_index), (4, 2));
+    assert_eq!(report.a, None);
+    assert_eq!(
+        report.b,
+        Some(DecodedCommand::Run {
+            unit: vec![b'A'],
+            count: 2
+        })
+    );
+    assert_eq!(diff_report(&a, &a, Size::Byte), None);
+}
+
+#[test]
+fn optimize_merges_commands_another_encoder_split() {
+    // Runs, then literals, written as two commands where one would do
+    let split_runs = [0x02, 0x41, 0x02, 0x41];
+    let optimized = optimize(&split_runs, Size::Byte).unwrap();
+    assert_eq!(optimized, vec![0x06, 0x41]);
+    assert_eq!(decompress(&optimized, Size::Byte).unwrap(), vec![0x41; 8]);
+
+    let split_literals = [0xFE, 0x41, 0x42, 0xFE, 0x43, 0x44];
+    let optimized = optimize(&split_literals, Size::Byte).unwrap();
+    assert_eq!(optimized, vec![0xFC, 0x41, 0x42, 0x43, 0x44]);
+
+    assert!(optimize(&[0x02], Size::Byte).is_err());
+}